@@ -0,0 +1,31 @@
+-- AlterTable
+ALTER TABLE `User` MODIFY `password` VARCHAR(191) NULL;
+
+-- CreateTable
+CREATE TABLE `OidcIdentity` (
+    `id` INTEGER NOT NULL AUTO_INCREMENT,
+    `issuer` VARCHAR(191) NOT NULL,
+    `subject` VARCHAR(191) NOT NULL,
+    `email` VARCHAR(191) NOT NULL,
+    `createdAt` DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    `userId` INTEGER NOT NULL,
+
+    UNIQUE INDEX `OidcIdentity_issuer_subject_key`(`issuer`, `subject`),
+    PRIMARY KEY (`id`)
+) DEFAULT CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
+
+-- CreateTable
+CREATE TABLE `OidcLoginRequest` (
+    `id` INTEGER NOT NULL AUTO_INCREMENT,
+    `state` VARCHAR(191) NOT NULL,
+    `codeVerifier` VARCHAR(191) NOT NULL,
+    `nonce` VARCHAR(191) NOT NULL,
+    `expiresAt` DATETIME(3) NOT NULL,
+    `createdAt` DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+
+    UNIQUE INDEX `OidcLoginRequest_state_key`(`state`),
+    PRIMARY KEY (`id`)
+) DEFAULT CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
+
+-- AddForeignKey
+ALTER TABLE `OidcIdentity` ADD CONSTRAINT `OidcIdentity_userId_fkey` FOREIGN KEY (`userId`) REFERENCES `User`(`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
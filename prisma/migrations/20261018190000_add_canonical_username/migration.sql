@@ -0,0 +1,10 @@
+-- AlterTable
+ALTER TABLE `User` ADD COLUMN `canonicalUsername` VARCHAR(191) NULL;
+
+-- Backfill, existing usernames are already unique under the case-insensitive collation
+UPDATE `User` SET `canonicalUsername` = LOWER(`username`);
+
+ALTER TABLE `User` MODIFY `canonicalUsername` VARCHAR(191) NOT NULL;
+
+-- CreateIndex
+CREATE UNIQUE INDEX `User_canonicalUsername_key` ON `User`(`canonicalUsername`);
@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE `User` ADD COLUMN `avatarKey` VARCHAR(191) NULL;
@@ -0,0 +1,17 @@
+-- AlterTable
+ALTER TABLE `User` ADD COLUMN `private` BOOLEAN NOT NULL DEFAULT false;
+
+-- CreateTable
+CREATE TABLE `FollowRequest` (
+    `requesterId` INTEGER NOT NULL,
+    `targetId` INTEGER NOT NULL,
+    `createdAt` DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+
+    PRIMARY KEY (`requesterId`, `targetId`)
+) DEFAULT CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
+
+-- AddForeignKey
+ALTER TABLE `FollowRequest` ADD CONSTRAINT `FollowRequest_requesterId_fkey` FOREIGN KEY (`requesterId`) REFERENCES `User`(`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE `FollowRequest` ADD CONSTRAINT `FollowRequest_targetId_fkey` FOREIGN KEY (`targetId`) REFERENCES `User`(`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
@@ -0,0 +1,19 @@
+-- CreateTable
+CREATE TABLE `UserSettings` (
+    `userId` INTEGER NOT NULL,
+    `locale` VARCHAR(191) NOT NULL DEFAULT 'en',
+    `timezone` VARCHAR(191) NOT NULL DEFAULT 'UTC',
+    `feedSort` ENUM('LATEST', 'POPULAR') NOT NULL DEFAULT 'LATEST',
+    `emailOnFollower` BOOLEAN NOT NULL DEFAULT true,
+    `emailOnFollowRequest` BOOLEAN NOT NULL DEFAULT true,
+    `emailOnComment` BOOLEAN NOT NULL DEFAULT true,
+    `emailOnFavorite` BOOLEAN NOT NULL DEFAULT false,
+    `hiddenTags` JSON NOT NULL,
+    `mutedWords` JSON NOT NULL,
+    `updatedAt` DATETIME(3) NOT NULL,
+
+    PRIMARY KEY (`userId`)
+) DEFAULT CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
+
+-- AddForeignKey
+ALTER TABLE `UserSettings` ADD CONSTRAINT `UserSettings_userId_fkey` FOREIGN KEY (`userId`) REFERENCES `User`(`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
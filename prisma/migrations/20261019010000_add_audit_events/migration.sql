@@ -0,0 +1,15 @@
+-- CreateTable
+CREATE TABLE `AuditEvent` (
+    `id` INTEGER NOT NULL AUTO_INCREMENT,
+    `action` ENUM('LOGIN_SUCCEEDED', 'LOGIN_FAILED', 'PASSWORD_CHANGED', 'EMAIL_CHANGED', 'TOKEN_ISSUED', 'USER_FOLLOWED', 'USER_UNFOLLOWED', 'ARTICLE_DELETED') NOT NULL,
+    `actorId` INTEGER NULL,
+    `target` VARCHAR(191) NULL,
+    `ip` VARCHAR(191) NULL,
+    `userAgent` VARCHAR(191) NULL,
+    `requestId` VARCHAR(191) NULL,
+    `createdAt` DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+
+    INDEX `AuditEvent_actorId_createdAt_idx`(`actorId`, `createdAt`),
+    INDEX `AuditEvent_action_createdAt_idx`(`action`, `createdAt`),
+    PRIMARY KEY (`id`)
+) DEFAULT CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
@@ -0,0 +1,14 @@
+-- CreateTable
+CREATE TABLE `ArticleSlug` (
+    `id` INTEGER NOT NULL AUTO_INCREMENT,
+    `slug` VARCHAR(191) NOT NULL,
+    `createdAt` DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    `articleId` INTEGER NOT NULL,
+
+    UNIQUE INDEX `ArticleSlug_slug_key`(`slug`),
+    INDEX `ArticleSlug_articleId_idx`(`articleId`),
+    PRIMARY KEY (`id`)
+) DEFAULT CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
+
+-- AddForeignKey
+ALTER TABLE `ArticleSlug` ADD CONSTRAINT `ArticleSlug_articleId_fkey` FOREIGN KEY (`articleId`) REFERENCES `Article`(`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
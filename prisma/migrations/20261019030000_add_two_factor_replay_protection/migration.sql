@@ -0,0 +1,17 @@
+-- AlterTable
+ALTER TABLE `User` ADD COLUMN `totpLastStep` BIGINT NULL;
+
+-- CreateTable
+CREATE TABLE `TwoFactorChallenge` (
+    `id` INTEGER NOT NULL AUTO_INCREMENT,
+    `jti` VARCHAR(191) NOT NULL,
+    `expiresAt` DATETIME(3) NOT NULL,
+    `createdAt` DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    `userId` INTEGER NOT NULL,
+
+    UNIQUE INDEX `TwoFactorChallenge_jti_key`(`jti`),
+    PRIMARY KEY (`id`)
+) DEFAULT CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci;
+
+-- AddForeignKey
+ALTER TABLE `TwoFactorChallenge` ADD CONSTRAINT `TwoFactorChallenge_userId_fkey` FOREIGN KEY (`userId`) REFERENCES `User`(`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
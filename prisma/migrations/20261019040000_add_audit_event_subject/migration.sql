@@ -0,0 +1,9 @@
+-- AlterTable
+ALTER TABLE `AuditEvent` ADD COLUMN `subjectId` INTEGER NULL;
+
+-- CreateIndex
+CREATE INDEX `AuditEvent_subjectId_createdAt_idx` ON `AuditEvent`(`subjectId`, `createdAt`);
+
+-- Failed logins were attributed to the account that was attacked, and kept the typed email
+UPDATE `AuditEvent` SET `subjectId` = `actorId`, `actorId` = NULL WHERE `action` = 'LOGIN_FAILED';
+UPDATE `AuditEvent` SET `target` = NULL WHERE `action` = 'LOGIN_FAILED' AND `target` <> 'two-factor code';
@@ -0,0 +1,3 @@
+-- The feed used to need profile:read, so tokens holding it keep working
+UPDATE `PersonalAccessToken` SET `scopes` = CONCAT(`scopes`, ' articles:read')
+WHERE CONCAT(' ', `scopes`, ' ') LIKE '% profile:read %';
@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE `AuditEvent` MODIFY `action` ENUM('LOGIN_SUCCEEDED', 'LOGIN_FAILED', 'PASSWORD_CHANGED', 'EMAIL_CHANGED', 'TOKEN_ISSUED', 'USER_FOLLOWED', 'USER_UNFOLLOWED', 'ARTICLE_UPDATED', 'ARTICLE_DELETED', 'COMMENT_DELETED') NOT NULL;
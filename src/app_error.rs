@@ -1,13 +1,39 @@
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
+    Json,
 };
 use prisma_client_rust::{
     prisma_errors::query_engine::{RecordNotFound, UniqueKeyViolation},
     QueryError,
 };
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
 use tracing::error;
 
+/// Field errors in the `{"errors": {"field": ["message"]}}` shape of the RealWorld spec.
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationErrors(BTreeMap<&'static str, Vec<String>>);
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.0.entry(field).or_default().push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), AppError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(self))
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum AppError {
     #[error("Prisma error: {0}")]
@@ -19,15 +45,32 @@ pub enum AppError {
     #[error("Unauthorized : {0}")]
     Unauthorized(String),
 
+    #[error("Forbidden : {0}")]
+    Forbidden(String),
+
     #[error("Bad request : {0}")]
     BadRequest(String),
 
+    #[error("Too many requests : {0}")]
+    TooManyRequests(String),
+
+    #[error("Validation failed : {0:?}")]
+    Validation(ValidationErrors),
+
     #[error("Internal server error: {0}")]
     Anyhow(#[from] anyhow::Error),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        if let AppError::Validation(errors) = self {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({ "errors": errors })),
+            )
+                .into_response();
+        }
+
         let status = match self {
             AppError::PrismaError(ref error) if error.is_prisma_error::<UniqueKeyViolation>() => {
                 StatusCode::CONFLICT
@@ -38,7 +81,10 @@ impl IntoResponse for AppError {
             AppError::PrismaError(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
             AppError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
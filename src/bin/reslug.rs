@@ -0,0 +1,33 @@
+//! One-off migration that regenerates article slugs with the current slug rules.
+//!
+//! Run with `--dry-run` to only log the slugs that would change.
+
+use realworld_axum_prisma::{
+    config::app_config::AppConfig, domain::articles::slug::Slug, prisma::PrismaClient,
+};
+use tracing::info;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = AppConfig::init();
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(&config.log_level))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let dry_run = std::env::args().any(|arg| arg == "--dry-run");
+
+    let prisma = PrismaClient::_builder().build().await?;
+    let changed = Slug::reslug_all(&prisma, dry_run)
+        .await
+        .map_err(|e| anyhow::anyhow!("reslugging failed: {:?}", e))?;
+
+    if dry_run {
+        info!("{} article slugs would change", changed);
+    } else {
+        info!("changed {} article slugs", changed);
+    }
+
+    Ok(())
+}
@@ -1,9 +1,20 @@
 extern crate dotenv;
 
 use dotenv::dotenv;
-use std::env;
+use prisma_client_rust::chrono::{DateTime, Utc};
+use std::{env, path::Path};
 
-use super::{db::DatabaseConfig, jwt::JwtConfig};
+use super::{
+    auth::AuthConfig,
+    db::DatabaseConfig,
+    jwt::{JwtConfig, JwtKey},
+    mailer::{MailerConfig, MailerKind},
+    oidc::OidcConfig,
+    password::{PasswordHashingConfig, PasswordPolicyConfig},
+    storage::{StorageConfig, StorageKind},
+};
+
+const DEFAULT_KID: &str = "default";
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
@@ -11,6 +22,13 @@ pub struct AppConfig {
     pub log_level: String,
     pub db: DatabaseConfig,
     pub jwt: JwtConfig,
+    pub auth: AuthConfig,
+    pub mailer: MailerConfig,
+    pub password: PasswordPolicyConfig,
+    pub password_hashing: PasswordHashingConfig,
+    pub storage: StorageConfig,
+    /// Social login is only enabled when `OIDC_ISSUER` is set.
+    pub oidc: Option<OidcConfig>,
 }
 
 impl AppConfig {
@@ -22,21 +40,211 @@ impl AppConfig {
                 url: get_env("DATABASE_URL"),
             },
             jwt: JwtConfig {
-                secret: get_env("JWT_SECRET"),
                 exp_in_sec: value_to_seconds(
                     get_env("JWT_EXP_VALUE").parse().unwrap(),
                     get_env("JWT_EXP_UNIT"),
-                )
+                ),
+                refresh_exp_in_sec: value_to_seconds(
+                    get_env("JWT_REFRESH_EXP_VALUE").parse().unwrap(),
+                    get_env("JWT_REFRESH_EXP_UNIT"),
+                ),
+                active_kid: get_env_optional("JWT_ACTIVE_KID")
+                    .unwrap_or_else(|| DEFAULT_KID.to_string()),
+                keys: load_jwt_keys(),
+                grace_in_sec: match get_env_optional("JWT_KEY_GRACE_VALUE") {
+                    Some(value) => value_to_seconds(
+                        value.parse().unwrap(),
+                        get_env("JWT_KEY_GRACE_UNIT"),
+                    ),
+                    None => 0,
+                },
+            },
+            auth: AuthConfig {
+                app_url: get_env("APP_URL"),
+                password_reset_exp_in_sec: value_to_seconds(
+                    get_env("PASSWORD_RESET_EXP_VALUE").parse().unwrap(),
+                    get_env("PASSWORD_RESET_EXP_UNIT"),
+                ),
+                email_verification_exp_in_sec: value_to_seconds(
+                    get_env("EMAIL_VERIFICATION_EXP_VALUE").parse().unwrap(),
+                    get_env("EMAIL_VERIFICATION_EXP_UNIT"),
+                ),
+                require_verified_email: get_env_optional("REQUIRE_VERIFIED_EMAIL")
+                    .is_some_and(|required| required == "true"),
+                totp_issuer: get_env_optional("TOTP_ISSUER")
+                    .unwrap_or_else(|| "Conduit".to_string()),
+                login_lockout_threshold: get_env_optional("LOGIN_LOCKOUT_THRESHOLD")
+                    .map(|threshold| threshold.parse().unwrap())
+                    .unwrap_or(5),
+                login_lockout_base_in_sec: get_env_optional("LOGIN_LOCKOUT_BASE_SECONDS")
+                    .map(|seconds| seconds.parse().unwrap())
+                    .unwrap_or(30),
+                login_lockout_max_in_sec: get_env_optional("LOGIN_LOCKOUT_MAX_SECONDS")
+                    .map(|seconds| seconds.parse().unwrap())
+                    .unwrap_or(3600),
+                trust_proxy_headers: get_env_optional("TRUST_PROXY_HEADERS")
+                    .is_some_and(|trusted| trusted == "true"),
+                trusted_proxy_hops: get_env_optional("TRUSTED_PROXY_HOPS")
+                    .map(|hops| hops.parse().unwrap())
+                    .unwrap_or(1),
+                account_retention_days: get_env_optional("ACCOUNT_RETENTION_DAYS")
+                    .map(|days| days.parse().unwrap())
+                    .unwrap_or(30),
+                account_purge_days: get_env_optional("ACCOUNT_PURGE_DAYS")
+                    .map(|days| days.parse().unwrap())
+                    .unwrap_or(90),
+                username_cooldown_days: get_env_optional("USERNAME_COOLDOWN_DAYS")
+                    .map(|days| days.parse().unwrap())
+                    .unwrap_or(30),
+            },
+            mailer: MailerConfig {
+                kind: match get_env("MAILER").as_str() {
+                    "smtp" => MailerKind::Smtp,
+                    "file" => MailerKind::File,
+                    _ => panic!("MAILER must be either smtp or file"),
+                },
+                from: get_env("MAIL_FROM"),
+                smtp_host: get_env_optional("SMTP_HOST")
+                    .unwrap_or_else(|| "localhost".to_string()),
+                smtp_port: get_env_optional("SMTP_PORT")
+                    .map(|port| port.parse().unwrap())
+                    .unwrap_or(1025),
+                smtp_tls: get_env_optional("SMTP_TLS").is_some_and(|tls| tls == "true"),
+                smtp_username: get_env_optional("SMTP_USERNAME"),
+                smtp_password: get_env_optional("SMTP_PASSWORD"),
+                outbox_dir: get_env_optional("MAIL_OUTBOX_DIR")
+                    .unwrap_or_else(|| "outbox".to_string()),
+            },
+            password: PasswordPolicyConfig {
+                min_length: get_env_optional("PASSWORD_MIN_LENGTH")
+                    .map(|length| length.parse().unwrap())
+                    .unwrap_or(8),
+                require_lowercase: get_env_optional("PASSWORD_REQUIRE_LOWERCASE")
+                    .is_some_and(|required| required == "true"),
+                require_uppercase: get_env_optional("PASSWORD_REQUIRE_UPPERCASE")
+                    .is_some_and(|required| required == "true"),
+                require_digit: get_env_optional("PASSWORD_REQUIRE_DIGIT")
+                    .is_some_and(|required| required == "true"),
+                require_symbol: get_env_optional("PASSWORD_REQUIRE_SYMBOL")
+                    .is_some_and(|required| required == "true"),
+                reject_personal_info: get_env_optional("PASSWORD_REJECT_PERSONAL_INFO")
+                    .map_or(true, |rejected| rejected == "true"),
+                breached_passwords_file: get_env_optional("BREACHED_PASSWORDS_FILE"),
             },
+            password_hashing: PasswordHashingConfig {
+                memory_cost_kib: get_env_optional("ARGON2_MEMORY_KIB")
+                    .map(|memory| memory.parse().unwrap())
+                    .unwrap_or(19456),
+                time_cost: get_env_optional("ARGON2_TIME_COST")
+                    .map(|iterations| iterations.parse().unwrap())
+                    .unwrap_or(2),
+                parallelism: get_env_optional("ARGON2_PARALLELISM")
+                    .map(|lanes| lanes.parse().unwrap())
+                    .unwrap_or(1),
+                pepper: get_env_optional("PASSWORD_PEPPER"),
+            },
+            storage: load_storage_config(),
+            oidc: load_oidc_config(),
         }
     }
 }
 
+/// Public URLs default to the `/uploads` route for `local` storage, and to the
+/// path-style bucket URL for `s3`.
+fn load_storage_config() -> StorageConfig {
+    let kind = match get_env_optional("STORAGE").as_deref() {
+        None | Some("local") => StorageKind::Local,
+        Some("s3") => StorageKind::S3,
+        _ => panic!("STORAGE must be either local or s3"),
+    };
+
+    let s3_bucket = get_env_optional("S3_BUCKET").unwrap_or_else(|| "conduit".to_string());
+    let s3_region = get_env_optional("S3_REGION").unwrap_or_else(|| "us-east-1".to_string());
+    let s3_endpoint = get_env_optional("S3_ENDPOINT")
+        .map(|endpoint| endpoint.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", s3_region));
+
+    let public_url = get_env_optional("STORAGE_PUBLIC_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| match kind {
+            StorageKind::Local => format!("http://localhost:{}/uploads", get_env("PORT")),
+            StorageKind::S3 => format!("{}/{}", s3_endpoint, s3_bucket),
+        });
+
+    StorageConfig {
+        kind,
+        public_url,
+        local_dir: get_env_optional("STORAGE_DIR").unwrap_or_else(|| "uploads".to_string()),
+        s3_bucket,
+        s3_region,
+        s3_endpoint,
+        s3_access_key_id: get_env_optional("S3_ACCESS_KEY_ID"),
+        s3_secret_access_key: get_env_optional("S3_SECRET_ACCESS_KEY"),
+        s3_path_style: get_env_optional("S3_PATH_STYLE").map_or(true, |path_style| path_style == "true"),
+    }
+}
+
+/// Loads asymmetric signing keys from `JWT_KEYS_DIR` when it is set, and falls back
+/// to a single HS256 key built from `JWT_SECRET` otherwise.
+///
+/// Retired keys are listed in `JWT_RETIRED_KIDS` as `kid@rfc3339,...`, where the
+/// timestamp is the moment the key was rotated out.
+fn load_jwt_keys() -> Vec<JwtKey> {
+    let Some(dir) = get_env_optional("JWT_KEYS_DIR") else {
+        return vec![JwtKey::from_secret(DEFAULT_KID, &get_env("JWT_SECRET"))];
+    };
+    let dir = Path::new(&dir);
+
+    let mut keys = vec![JwtKey::from_pem_dir(
+        dir,
+        &get_env("JWT_ACTIVE_KID"),
+        true,
+        None,
+    )];
+
+    if let Some(retired) = get_env_optional("JWT_RETIRED_KIDS") {
+        for entry in retired.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (kid, retired_at) = entry
+                .split_once('@')
+                .unwrap_or_else(|| panic!("JWT_RETIRED_KIDS entry {} must be kid@rfc3339", entry));
+            let retired_at = DateTime::parse_from_rfc3339(retired_at)
+                .unwrap_or_else(|e| panic!("invalid rotation time for kid {}: {}", kid, e))
+                .with_timezone(&Utc);
+
+            keys.push(JwtKey::from_pem_dir(dir, kid, false, Some(retired_at)));
+        }
+    }
+
+    keys
+}
+
+fn load_oidc_config() -> Option<OidcConfig> {
+    let issuer = get_env_optional("OIDC_ISSUER")?;
+    let issuer = issuer.trim_end_matches('/').to_string();
+
+    Some(OidcConfig {
+        client_id: get_env("OIDC_CLIENT_ID"),
+        client_secret: get_env_optional("OIDC_CLIENT_SECRET"),
+        redirect_uri: get_env_optional("OIDC_REDIRECT_URI")
+            .unwrap_or_else(|| format!("{}/oidc/callback", get_env("APP_URL"))),
+        discovery_url: get_env_optional("OIDC_DISCOVERY_URL")
+            .unwrap_or_else(|| format!("{}/.well-known/openid-configuration", issuer)),
+        scopes: get_env_optional("OIDC_SCOPES")
+            .unwrap_or_else(|| "openid email profile".to_string()),
+        issuer,
+    })
+}
+
 pub fn get_env(key: &str) -> String {
     dotenv().ok();
     env::var(key).unwrap_or_else(|_| panic!("{} must be set", key))
 }
 
+pub fn get_env_optional(key: &str) -> Option<String> {
+    dotenv().ok();
+    env::var(key).ok().filter(|value| !value.is_empty())
+}
+
 pub fn value_to_seconds(value: i64, unit: String) -> i64 {
 
     match unit.as_str() {
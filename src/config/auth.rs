@@ -0,0 +1,27 @@
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// Base URL of the frontend, used to build links sent by email.
+    pub app_url: String,
+    pub password_reset_exp_in_sec: i64,
+    pub email_verification_exp_in_sec: i64,
+    /// Stops users with an unverified email from publishing articles or comments.
+    pub require_verified_email: bool,
+    /// Issuer shown in authenticator apps.
+    pub totp_issuer: String,
+    /// Failed logins allowed per account or IP before it gets locked out.
+    pub login_lockout_threshold: i32,
+    /// Lockout after the threshold is reached, doubled on every further failure.
+    pub login_lockout_base_in_sec: i64,
+    pub login_lockout_max_in_sec: i64,
+    /// Take the client IP from `X-Forwarded-For` instead of the socket address.
+    pub trust_proxy_headers: bool,
+    /// Reverse proxies in front of the app. Each one appends to `X-Forwarded-For`, so the
+    /// client IP is this many entries from the right; anything further left is client input.
+    pub trusted_proxy_hops: usize,
+    /// Days a deleted account keeps its username and email reserved.
+    pub account_retention_days: i64,
+    /// Days after which a deleted account is removed from the database.
+    pub account_purge_days: i64,
+    /// Days a username given up by a rename stays locked for everyone else.
+    pub username_cooldown_days: i64,
+}
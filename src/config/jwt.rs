@@ -1,5 +1,134 @@
+use std::{fmt, fs, path::Path};
+
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use prisma_client_rust::chrono::{self, DateTime, Utc};
+
 #[derive(Debug, Clone)]
 pub struct JwtConfig {
-    pub secret: String,
     pub exp_in_sec: i64,
+    pub refresh_exp_in_sec: i64,
+    pub active_kid: String,
+    pub keys: Vec<JwtKey>,
+    /// How long a retired key keeps verifying tokens after it was rotated out.
+    pub grace_in_sec: i64,
+}
+
+#[derive(Clone)]
+pub struct JwtKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub encoding_key: Option<EncodingKey>,
+    pub decoding_key: DecodingKey,
+    /// PEM encoded public key, `None` for shared secrets which must never be published.
+    pub public_pem: Option<String>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .field("retired_at", &self.retired_at)
+            .finish_non_exhaustive()
+    }
+}
+
+impl JwtConfig {
+    pub fn active_key(&self) -> &JwtKey {
+        self.keys
+            .iter()
+            .find(|key| key.kid == self.active_kid)
+            .expect("active JWT key must be loaded")
+    }
+
+    /// Returns the key for `kid` if it may still be used to verify tokens.
+    pub fn verification_key(&self, kid: Option<&str>) -> Option<&JwtKey> {
+        let kid = kid.unwrap_or(self.active_kid.as_str());
+
+        self.keys
+            .iter()
+            .find(|key| key.kid == kid)
+            .filter(|key| self.is_within_grace(key))
+    }
+
+    /// Keys whose public half can be served in the JWKS document.
+    pub fn published_keys(&self) -> impl Iterator<Item = &JwtKey> {
+        self.keys
+            .iter()
+            .filter(|key| key.public_pem.is_some() && self.is_within_grace(key))
+    }
+
+    fn is_within_grace(&self, key: &JwtKey) -> bool {
+        match key.retired_at {
+            Some(retired_at) => {
+                chrono::Utc::now() < retired_at + chrono::Duration::seconds(self.grace_in_sec)
+            }
+            None => true,
+        }
+    }
+}
+
+impl JwtKey {
+    pub fn from_secret(kid: &str, secret: &str) -> Self {
+        Self {
+            kid: kid.to_string(),
+            algorithm: Algorithm::HS256,
+            encoding_key: Some(EncodingKey::from_secret(secret.as_ref())),
+            decoding_key: DecodingKey::from_secret(secret.as_ref()),
+            public_pem: None,
+            retired_at: None,
+        }
+    }
+
+    /// Loads `<kid>.pub.pem` from `dir`, and `<kid>.pem` as well when the key signs tokens.
+    pub fn from_pem_dir(
+        dir: &Path,
+        kid: &str,
+        signing: bool,
+        retired_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        let public_pem = fs::read_to_string(dir.join(format!("{}.pub.pem", kid)))
+            .unwrap_or_else(|e| panic!("failed to read public key for kid {}: {}", kid, e));
+        let algorithm = Self::detect_algorithm(&public_pem)
+            .unwrap_or_else(|| panic!("unsupported public key type for kid {}", kid));
+
+        let decoding_key = match algorithm {
+            Algorithm::RS256 => DecodingKey::from_rsa_pem(public_pem.as_bytes()),
+            _ => DecodingKey::from_ed_pem(public_pem.as_bytes()),
+        }
+        .unwrap_or_else(|e| panic!("invalid public key for kid {}: {}", kid, e));
+
+        let encoding_key = signing.then(|| {
+            let private_pem = fs::read(dir.join(format!("{}.pem", kid)))
+                .unwrap_or_else(|e| panic!("failed to read private key for kid {}: {}", kid, e));
+
+            match algorithm {
+                Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem),
+                _ => EncodingKey::from_ed_pem(&private_pem),
+            }
+            .unwrap_or_else(|e| panic!("invalid private key for kid {}: {}", kid, e))
+        });
+
+        Self {
+            kid: kid.to_string(),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            public_pem: Some(public_pem),
+            retired_at,
+        }
+    }
+
+    fn detect_algorithm(public_pem: &str) -> Option<Algorithm> {
+        use rsa::pkcs8::DecodePublicKey;
+
+        if rsa::RsaPublicKey::from_public_key_pem(public_pem).is_ok() {
+            Some(Algorithm::RS256)
+        } else if ed25519_dalek::VerifyingKey::from_public_key_pem(public_pem).is_ok() {
+            Some(Algorithm::EdDSA)
+        } else {
+            None
+        }
+    }
 }
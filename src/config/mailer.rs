@@ -0,0 +1,17 @@
+#[derive(Debug, Clone)]
+pub enum MailerKind {
+    Smtp,
+    File,
+}
+
+#[derive(Debug, Clone)]
+pub struct MailerConfig {
+    pub kind: MailerKind,
+    pub from: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_tls: bool,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub outbox_dir: String,
+}
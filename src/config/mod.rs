@@ -1,13 +1,23 @@
 use std::sync::Arc;
 use lazy_static::lazy_static;
 
+use crate::{domain::users::password::BreachedPasswords, mailer::Mailer, storage::Storage};
+
 use self::app_config::AppConfig;
 
 pub mod app_config;
+pub mod auth;
 pub mod db;
 pub mod jwt;
+pub mod mailer;
+pub mod oidc;
+pub mod password;
+pub mod storage;
 
 #[derive(Clone)]
 pub struct AppContext {
     pub config: Arc<AppConfig>,
+    pub mailer: Arc<dyn Mailer>,
+    pub breached_passwords: Arc<BreachedPasswords>,
+    pub storage: Arc<dyn Storage>,
 }
\ No newline at end of file
@@ -0,0 +1,13 @@
+/// OpenID Connect identity provider used for social login.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    /// Confidential clients authenticate with a secret, public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    /// Frontend page the provider redirects back to with `code` and `state`.
+    pub redirect_uri: String,
+    /// Defaults to `<issuer>/.well-known/openid-configuration`.
+    pub discovery_url: String,
+    pub scopes: String,
+}
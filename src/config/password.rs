@@ -0,0 +1,22 @@
+#[derive(Debug, Clone)]
+pub struct PasswordPolicyConfig {
+    pub min_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Rejects passwords containing the username or the email address.
+    pub reject_personal_info: bool,
+    /// File with one breached password, or SHA-1 hash of one, per line.
+    pub breached_passwords_file: Option<String>,
+}
+
+/// Argon2id cost parameters, defaulting to the OWASP recommended minimum.
+#[derive(Debug, Clone)]
+pub struct PasswordHashingConfig {
+    pub memory_cost_kib: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    /// Server-side secret mixed into every hash, kept out of the database.
+    pub pepper: Option<String>,
+}
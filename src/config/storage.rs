@@ -0,0 +1,21 @@
+#[derive(Debug, Clone)]
+pub enum StorageKind {
+    Local,
+    S3,
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    pub kind: StorageKind,
+    /// Base URL uploaded files are served from.
+    pub public_url: String,
+    /// Directory for `local` storage, served under `/uploads`.
+    pub local_dir: String,
+    pub s3_bucket: String,
+    pub s3_region: String,
+    pub s3_endpoint: String,
+    pub s3_access_key_id: Option<String>,
+    pub s3_secret_access_key: Option<String>,
+    /// Addresses the bucket as `<endpoint>/<bucket>`, as MinIO and most S3 clones expect.
+    pub s3_path_style: bool,
+}
@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+use super::scope::Scope;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenCreateInput {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_in_days: Option<i64>,
+}
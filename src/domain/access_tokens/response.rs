@@ -0,0 +1,34 @@
+use prisma_client_rust::chrono::FixedOffset;
+use serde::{Deserialize, Serialize};
+
+use crate::prisma::personal_access_token;
+
+use super::scope::Scope;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessToken {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<::prisma_client_rust::chrono::DateTime<FixedOffset>>,
+    pub last_used_at: Option<::prisma_client_rust::chrono::DateTime<FixedOffset>>,
+    pub created_at: ::prisma_client_rust::chrono::DateTime<FixedOffset>,
+    /// Only returned once, right after the token was created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<personal_access_token::Data> for AccessToken {
+    fn from(data: personal_access_token::Data) -> Self {
+        Self {
+            id: data.id,
+            name: data.name,
+            scopes: Scope::split(&data.scopes),
+            expires_at: data.expires_at,
+            last_used_at: data.last_used_at,
+            created_at: data.created_at,
+            token: None,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Permission granted to a personal access token. Session logins hold every scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "articles:read")]
+    ArticlesRead,
+    #[serde(rename = "articles:write")]
+    ArticlesWrite,
+    #[serde(rename = "comments:write")]
+    CommentsWrite,
+    #[serde(rename = "favorites:write")]
+    FavoritesWrite,
+    #[serde(rename = "follows:write")]
+    FollowsWrite,
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 7] = [
+        Scope::ArticlesRead,
+        Scope::ArticlesWrite,
+        Scope::CommentsWrite,
+        Scope::FavoritesWrite,
+        Scope::FollowsWrite,
+        Scope::ProfileRead,
+        Scope::ProfileWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ArticlesRead => "articles:read",
+            Scope::ArticlesWrite => "articles:write",
+            Scope::CommentsWrite => "comments:write",
+            Scope::FavoritesWrite => "favorites:write",
+            Scope::FollowsWrite => "follows:write",
+            Scope::ProfileRead => "profile:read",
+            Scope::ProfileWrite => "profile:write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Scope> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    /// Scopes are stored as a space separated list.
+    pub fn join(scopes: &[Scope]) -> String {
+        scopes
+            .iter()
+            .map(Scope::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn split(value: &str) -> Vec<Scope> {
+        value.split_whitespace().filter_map(Scope::parse).collect()
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
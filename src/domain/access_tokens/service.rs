@@ -0,0 +1,185 @@
+use axum::{extract::Path, Extension, Json};
+use prisma_client_rust::{chrono, Direction};
+use std::sync::Arc;
+
+use crate::{
+    app_error::AppError,
+    domain::{audit::service::AuditService, users::token::TokenService},
+    extractor::{AuthUser, ClientInfo},
+    prisma::{personal_access_token, user, AuditAction, PrismaClient},
+};
+
+use super::{
+    request::AccessTokenCreateInput, response::AccessToken, scope::Scope, AccessTokenBody,
+    AccessTokensBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+/// Prefix telling personal access tokens apart from JWTs in the Authorization header.
+pub const ACCESS_TOKEN_PREFIX: &str = "cdt_";
+const ACCESS_TOKEN_LENGTH: usize = 40;
+const LAST_USED_RESOLUTION_IN_SEC: i64 = 300;
+
+pub struct AccessTokensService;
+
+impl AccessTokensService {
+    pub async fn create_access_token(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        client: ClientInfo,
+        Json(input): Json<AccessTokenBody<AccessTokenCreateInput>>,
+    ) -> Result<Json<AccessTokenBody<AccessToken>>, AppError> {
+        let AccessTokenBody {
+            access_token:
+                AccessTokenCreateInput {
+                    name,
+                    scopes,
+                    expires_in_days,
+                },
+        } = input;
+
+        // A leaked token must not be able to mint more tokens
+        auth_user.require_session()?;
+
+        if name.trim().is_empty() {
+            return Err(AppError::BadRequest(String::from(
+                "Token name must not be empty",
+            )));
+        }
+
+        if scopes.is_empty() {
+            return Err(AppError::BadRequest(String::from(
+                "Token needs at least one scope",
+            )));
+        }
+
+        let expires_at = match expires_in_days {
+            Some(days) if days <= 0 => {
+                return Err(AppError::BadRequest(String::from(
+                    "Token expiry must be in the future",
+                )))
+            }
+            Some(days) => Some((chrono::Utc::now() + chrono::Duration::days(days)).into()),
+            None => None,
+        };
+
+        let token = format!(
+            "{}{}",
+            ACCESS_TOKEN_PREFIX,
+            TokenService::random_string(ACCESS_TOKEN_LENGTH)
+        );
+
+        let data = prisma
+            .personal_access_token()
+            .create(
+                name,
+                TokenService::hash_token(&token),
+                Scope::join(&scopes),
+                user::id::equals(auth_user.user_id),
+                vec![personal_access_token::expires_at::set(expires_at)],
+            )
+            .exec()
+            .await?;
+
+        AuditService::record(
+            &prisma,
+            &client,
+            AuditAction::TokenIssued,
+            Some(auth_user.user_id),
+            None,
+            Some(&format!("access token {}", data.name)),
+        )
+        .await;
+
+        let mut access_token: AccessToken = data.into();
+        access_token.token = Some(token);
+
+        Ok(Json::from(AccessTokenBody { access_token }))
+    }
+
+    pub async fn get_access_tokens(
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<AccessTokensBody<AccessToken>>, AppError> {
+        auth_user.require_session()?;
+
+        let access_tokens = prisma
+            .personal_access_token()
+            .find_many(vec![personal_access_token::user_id::equals(
+                auth_user.user_id,
+            )])
+            .order_by(personal_access_token::created_at::order(Direction::Desc))
+            .exec()
+            .await?
+            .into_iter()
+            .map(AccessToken::from)
+            .collect();
+
+        Ok(Json::from(AccessTokensBody { access_tokens }))
+    }
+
+    pub async fn delete_access_token(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        Path(token_id): Path<i32>,
+    ) -> Result<Json<String>, AppError> {
+        auth_user.require_session()?;
+
+        let deleted = prisma
+            .personal_access_token()
+            .delete_many(vec![
+                personal_access_token::id::equals(token_id),
+                personal_access_token::user_id::equals(auth_user.user_id),
+            ])
+            .exec()
+            .await?;
+
+        if deleted == 0 {
+            return Err(AppError::NotFound(String::from("Access token not found")));
+        }
+
+        Ok(Json::from("Access token revoked".to_string()))
+    }
+
+    /// Resolves a personal access token from the Authorization header.
+    pub async fn authenticate(prisma: &PrismaClient, token: &str) -> Result<AuthUser, AppError> {
+        let data = prisma
+            .personal_access_token()
+            .find_unique(personal_access_token::token_hash::equals(
+                TokenService::hash_token(token),
+            ))
+            .exec()
+            .await?
+            .ok_or(AppError::Unauthorized(String::from("Invalid access token")))?;
+
+        let now = chrono::Utc::now();
+
+        if data.expires_at.is_some_and(|expires_at| expires_at < now) {
+            return Err(AppError::Unauthorized(String::from(
+                "Access token is expired",
+            )));
+        }
+
+        let stale = data.last_used_at.map_or(true, |last_used_at| {
+            last_used_at < now - chrono::Duration::seconds(LAST_USED_RESOLUTION_IN_SEC)
+        });
+
+        if stale {
+            prisma
+                .personal_access_token()
+                .update_many(
+                    vec![personal_access_token::id::equals(data.id)],
+                    vec![personal_access_token::last_used_at::set(Some(now.into()))],
+                )
+                .exec()
+                .await?;
+        }
+
+        Ok(AuthUser {
+            user_id: data.user_id,
+            session_id: None,
+            scopes: Scope::split(&data.scopes),
+        })
+    }
+}
@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 pub mod request;
 pub mod response;
 pub mod service;
+pub mod slug;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ArticleBody<T> {
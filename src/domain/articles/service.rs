@@ -1,24 +1,37 @@
 use axum::{
-    extract::{Path, Query},
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
     Extension, Json,
 };
 use prisma_client_rust::chrono;
 use prisma_client_rust::Direction;
 use std::sync::Arc;
+use tracing::info;
 
 use crate::{
     app_error::AppError,
-    domain::profiles::service::ProfilesService,
-    extractor::{AuthUser, OptionalAuthUser},
+    config::AppContext,
+    domain::{
+        access_tokens::scope::Scope,
+        audit::service::AuditService,
+        profiles::{relationship::RelationshipsService, service::ProfilesService},
+        users::{
+            permission::Permission, service::UsersService, settings::SettingsService,
+            username::Username,
+        },
+    },
+    extractor::{AuthUser, ClientInfo, OptionalAuthUser},
     prisma::{
-        self, article, article_tag, comment, user, user_favorite_article,
-        user_follows::followed_by_id, PrismaClient,
+        self, article, article_slug, article_tag, comment, user, user_favorite_article,
+        user_follows::followed_by_id, AuditAction, FeedSort, PrismaClient,
     },
 };
 
 use super::{
     request::{ArticleCreateInput, ArticleListQuery, ArticleUpdateInput, CommentCreateInput},
     response::{Article, Comment},
+    slug::Slug,
     ArticleBody, ArticlesBody, CommentBody, CommentsBody,
 };
 
@@ -27,28 +40,94 @@ type Prisma = Extension<Arc<PrismaClient>>;
 pub struct ArticlesService;
 
 impl ArticlesService {
-    fn slugify(title: &str) -> String {
-        title
-            .chars()
-            .map(|c| match c {
-                ' ' => '-',
-                _ => c,
-            })
-            .collect::<String>()
-            .to_lowercase()
-    }
-
-    fn check_author(
+    /// Lets the author through, as well as moderators acting on someone else's article.
+    async fn check_author(
+        prisma: &Prisma,
         auth_user: &AuthUser,
         article: &prisma::article::Data,
-    ) -> Result<bool, AppError> {
+        action: &str,
+    ) -> Result<(), AppError> {
         if article.author_id == auth_user.user_id {
-            Ok(true)
-        } else {
-            Err(AppError::BadRequest(String::from(
-                "You are not the author of this article",
-            )))
+            return Ok(());
         }
+
+        if auth_user
+            .has_permission(prisma, Permission::ModerateArticles)
+            .await?
+        {
+            info!(
+                "moderator {} {} article {} by user {}",
+                auth_user.user_id, action, article.id, article.author_id
+            );
+            return Ok(());
+        }
+
+        Err(AppError::Forbidden(String::from(
+            "You are not the author of this article",
+        )))
+    }
+
+    /// Matches articles by the author's current or former username.
+    async fn author_filter(
+        prisma: &PrismaClient,
+        author: &str,
+    ) -> Result<article::WhereParam, AppError> {
+        Ok(match Username::resolve(prisma, author).await? {
+            Some(data) => article::author_id::equals(data.id),
+            None => article::author::is(vec![user::canonical_username::equals(
+                Username::canonical(author),
+            )]),
+        })
+    }
+
+    /// Finds a live article by its current or a previous slug. Articles of deleted accounts
+    /// are hidden.
+    async fn find_article(prisma: &Prisma, slug: &str) -> Result<article::Data, AppError> {
+        let current = prisma
+            .article()
+            .find_first(vec![
+                article::slug::equals(slug.to_string()),
+                article::author::is(vec![user::deleted_at::equals(None)]),
+            ])
+            .with(article::author::fetch())
+            .exec()
+            .await?;
+
+        if let Some(article) = current {
+            return Ok(article);
+        }
+
+        prisma
+            .article()
+            .find_first(vec![
+                article::previous_slugs::some(vec![article_slug::slug::equals(slug.to_string())]),
+                article::deleted_at::equals(None),
+                article::author::is(vec![user::deleted_at::equals(None)]),
+            ])
+            .with(article::author::fetch())
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("Article not found")))
+    }
+
+    /// Like `find_article`, but articles of private authors are only found by their
+    /// approved followers.
+    async fn find_visible_article(
+        prisma: &Prisma,
+        slug: &str,
+        viewer_id: Option<i32>,
+    ) -> Result<article::Data, AppError> {
+        let article = Self::find_article(prisma, slug).await?;
+
+        let author = article
+            .author
+            .as_deref()
+            .ok_or(AppError::NotFound(String::from("Article not found")))?;
+        if !ProfilesService::can_view(prisma, author, viewer_id).await? {
+            return Err(AppError::NotFound(String::from("Article not found")));
+        }
+
+        Ok(article)
     }
 
     async fn check_favorited(
@@ -82,6 +161,7 @@ impl ArticlesService {
     pub async fn create_article(
         auth_user: AuthUser,
         prisma: Prisma,
+        ctx: State<AppContext>,
         Json(input): Json<ArticleBody<ArticleCreateInput>>,
     ) -> Result<Json<ArticleBody<Article>>, AppError> {
         let ArticleBody {
@@ -94,19 +174,25 @@ impl ArticlesService {
                 },
         } = input;
 
-        let article = prisma
-            .article()
-            .create(
-                Self::slugify(title.as_str()),
-                title,
-                description,
-                body,
-                user::id::equals(auth_user.user_id),
-                vec![],
-            )
-            .with(article::author::fetch())
-            .exec()
-            .await?;
+        auth_user.require_scope(Scope::ArticlesWrite)?;
+
+        UsersService::check_email_verified(&prisma, &ctx, &auth_user).await?;
+
+        let article = Slug::write_unique(&prisma, &title, None, |slug| {
+            prisma
+                .article()
+                .create(
+                    slug,
+                    title.clone(),
+                    description.clone(),
+                    body.clone(),
+                    user::id::equals(auth_user.user_id),
+                    vec![],
+                )
+                .with(article::author::fetch())
+                .exec()
+        })
+        .await?;
 
         if let Some(tag_list) = tag_list.clone() {
             let _ = prisma
@@ -131,6 +217,7 @@ impl ArticlesService {
     pub async fn update_article(
         auth_user: AuthUser,
         prisma: Prisma,
+        client: ClientInfo,
         Path(slug): Path<String>,
         Json(input): Json<ArticleBody<ArticleUpdateInput>>,
     ) -> Result<Json<ArticleBody<Article>>, AppError> {
@@ -143,42 +230,51 @@ impl ArticlesService {
                 },
         } = input;
 
-        let article = prisma
-            .article()
-            .find_unique(article::slug::equals(slug.clone()))
-            .with(article::author::fetch())
-            .exec()
-            .await?
-            .ok_or(AppError::NotFound(String::from("Article not found")))?;
-
-        Self::check_author(&auth_user, &article)?;
-
-        let updated_article = prisma
-            .article()
-            .update(
-                article::slug::equals(slug.clone()),
-                vec![
-                    match &title {
-                        Some(title) => article::slug::set(Self::slugify(title.as_str())),
-                        None => article::slug::set(article.slug),
-                    },
-                    match title {
-                        Some(title) => article::title::set(title),
-                        None => article::title::set(article.title),
-                    },
-                    match description {
-                        Some(description) => article::description::set(description),
-                        None => article::description::set(article.description),
-                    },
-                    match body {
-                        Some(body) => article::body::set(body),
-                        None => article::body::set(article.body),
-                    },
-                ],
+        auth_user.require_scope(Scope::ArticlesWrite)?;
+
+        let article = Self::find_article(&prisma, &slug).await?;
+
+        Self::check_author(&prisma, &auth_user, &article, "updated").await?;
+
+        let update = |slug: String| {
+            prisma
+                .article()
+                .update(
+                    article::id::equals(article.id),
+                    vec![
+                        article::slug::set(slug),
+                        article::title::set(title.clone().unwrap_or_else(|| article.title.clone())),
+                        article::description::set(
+                            description
+                                .clone()
+                                .unwrap_or_else(|| article.description.clone()),
+                        ),
+                        article::body::set(body.clone().unwrap_or_else(|| article.body.clone())),
+                    ],
+                )
+                .with(article::author::fetch())
+                .exec()
+        };
+
+        let updated_article = match &title {
+            Some(title) => Slug::write_unique(&prisma, title, Some(article.id), update).await?,
+            None => update(article.slug.clone()).await?,
+        };
+
+        Slug::record_rename(&prisma, article.id, &article.slug, &updated_article.slug).await?;
+
+        // Authors edit their articles all the time, only moderator edits are worth an event
+        if article.author_id != auth_user.user_id {
+            AuditService::record(
+                &prisma,
+                &client,
+                AuditAction::ArticleUpdated,
+                Some(auth_user.user_id),
+                Some(article.author_id),
+                Some(&updated_article.slug),
             )
-            .with(article::author::fetch())
-            .exec()
-            .await?;
+            .await;
+        }
 
         Ok(Json::from(ArticleBody {
             article: updated_article.to_article(false, false),
@@ -188,60 +284,77 @@ impl ArticlesService {
     pub async fn delete_article(
         auth_user: AuthUser,
         prisma: Prisma,
+        client: ClientInfo,
         Path(slug): Path<String>,
     ) -> Result<Json<String>, AppError> {
-        let article = prisma
-            .article()
-            .find_unique(article::slug::equals(slug.clone()))
-            .with(article::author::fetch())
-            .exec()
-            .await?
-            .ok_or(AppError::NotFound(String::from("Article not found")))?;
+        auth_user.require_scope(Scope::ArticlesWrite)?;
+
+        let article = Self::find_article(&prisma, &slug).await?;
 
-        Self::check_author(&auth_user, &article)?;
+        Self::check_author(&prisma, &auth_user, &article, "deleted").await?;
 
         let _ = prisma
             .article()
             .update(
-                article::slug::equals(slug.clone()),
+                article::id::equals(article.id),
                 vec![
-                    article::slug::set(Self::slug_hash(slug.as_str()).to_string()),
+                    article::slug::set(Self::slug_hash(article.slug.as_str()).to_string()),
                     article::deleted_at::set(Some(chrono::Utc::now().into())),
                 ],
             )
             .exec()
             .await?;
 
+        // Frees the previous slugs along with the current one
+        prisma
+            .article_slug()
+            .delete_many(vec![article_slug::article_id::equals(article.id)])
+            .exec()
+            .await?;
+
+        AuditService::record(
+            &prisma,
+            &client,
+            AuditAction::ArticleDeleted,
+            Some(auth_user.user_id),
+            Some(article.author_id).filter(|author_id| *author_id != auth_user.user_id),
+            Some(&article.slug),
+        )
+        .await;
+
         Ok(Json::from("Article deleted".to_string()))
     }
 
+    /// Previous slugs find the article too. The `slug` in the response is always the current
+    /// one, and a `Link` header with `rel="canonical"` points there when an old one was used.
     pub async fn get_article(
         auth_user: OptionalAuthUser,
         prisma: Prisma,
         Path(slug): Path<String>,
-    ) -> Result<Json<ArticleBody<Article>>, AppError> {
-        let article = prisma
-            .article()
-            .find_unique(article::slug::equals(slug))
-            .with(article::author::fetch())
-            .exec()
-            .await?
-            .ok_or(AppError::NotFound(String::from("Article not found")))?;
-
-        if let Some(user) = auth_user.0 {
-            let favorited = Self::check_favorited(&prisma, &user, article.id).await?;
-
-            let followed =
-                ProfilesService::check_following(&prisma, &user, article.author_id).await?;
-
-            return Ok(Json::from(ArticleBody {
-                article: article.to_article(favorited, followed),
-            }));
+    ) -> Result<Response, AppError> {
+        let auth_user = auth_user.with_scope(Scope::ArticlesRead);
+        let viewer_id = auth_user.0.as_ref().map(|user| user.user_id);
+        let article = Self::find_visible_article(&prisma, &slug, viewer_id).await?;
+
+        let (favorited, followed) = match auth_user.0 {
+            Some(user) => (
+                Self::check_favorited(&prisma, &user, article.id).await?,
+                ProfilesService::check_following(&prisma, &user, article.author_id).await?,
+            ),
+            None => (false, false),
+        };
+
+        let canonical_slug = article.slug.clone();
+        let body = Json::from(ArticleBody {
+            article: article.to_article(favorited, followed),
+        });
+
+        if canonical_slug != slug {
+            let link = format!("</api/articles/{}>; rel=\"canonical\"", canonical_slug);
+            return Ok(([(header::LINK, link)], body).into_response());
         }
 
-        Ok(Json::from(ArticleBody {
-            article: article.to_article(false, false),
-        }))
+        Ok(body.into_response())
     }
 
     pub async fn get_articles(
@@ -249,23 +362,42 @@ impl ArticlesService {
         prisma: Prisma,
         Query(query): Query<ArticleListQuery>,
     ) -> Result<Json<ArticlesBody<Article>>, AppError> {
+        let auth_user = auth_user.with_scope(Scope::ArticlesRead);
         let mut filter: Vec<prisma::article::WhereParam> = Vec::new();
 
+        let viewer_id = auth_user.0.as_ref().map(|user| user.user_id);
+        filter.push(article::author::is(vec![ProfilesService::visible_to(
+            viewer_id,
+        )]));
+
+        if let Some(user) = &auth_user.0 {
+            filter.push(article::author::is(vec![
+                RelationshipsService::not_muted_by(user.user_id),
+            ]));
+
+            if let Some(settings) = SettingsService::find(&prisma, user.user_id).await? {
+                filter.extend(SettingsService::article_filters(&settings));
+            }
+        }
+
         if let Some(tag) = query.tag {
             filter.push(article::tags::some(vec![article_tag::tag::equals(tag)]))
         }
 
         if let Some(author) = query.author {
-            filter.push(article::author::is(vec![user::username::equals(author)]))
+            filter.push(Self::author_filter(&prisma, &author).await?)
         }
 
         if let Some(favorited) = query.favorited {
             filter.push(article::favorited_by::some(vec![
-                user_favorite_article::user::is(vec![user::username::equals(favorited)]),
+                user_favorite_article::user::is(vec![user::canonical_username::equals(
+                    Username::canonical(&favorited),
+                )]),
             ]))
         }
 
         filter.push(article::deleted_at::equals(None));
+        filter.push(article::author::is(vec![user::deleted_at::equals(None)]));
 
         let _articles = prisma
             .article()
@@ -311,27 +443,42 @@ impl ArticlesService {
         prisma: Prisma,
         Query(query): Query<ArticleListQuery>,
     ) -> Result<Json<ArticlesBody<Article>>, AppError> {
+        auth_user.require_scope(Scope::ArticlesRead)?;
+
         let mut filter: Vec<prisma::article::WhereParam> = Vec::new();
 
+        filter.push(article::author::is(vec![
+            RelationshipsService::not_muted_by(auth_user.user_id),
+        ]));
+
+        let settings = SettingsService::find(&prisma, auth_user.user_id).await?;
+        if let Some(settings) = &settings {
+            filter.extend(SettingsService::article_filters(settings));
+        }
+
         if let Some(tag) = query.tag {
             filter.push(article::tags::some(vec![article_tag::tag::equals(tag)]))
         }
 
         if let Some(author) = query.author {
-            filter.push(article::author::is(vec![user::username::equals(author)]))
+            filter.push(Self::author_filter(&prisma, &author).await?)
         }
 
         if let Some(favorited) = query.favorited {
             filter.push(article::favorited_by::some(vec![
-                user_favorite_article::user::is(vec![user::username::equals(favorited)]),
+                user_favorite_article::user::is(vec![user::canonical_username::equals(
+                    Username::canonical(&favorited),
+                )]),
             ]))
         }
 
+        // Only approved follows exist as rows, so private authors need no extra check here
         filter.push(article::author::is(vec![user::followed_by::some(vec![
             followed_by_id::equals(auth_user.user_id),
         ])]));
 
         filter.push(article::deleted_at::equals(None));
+        filter.push(article::author::is(vec![user::deleted_at::equals(None)]));
 
         let _articles = prisma
             .article()
@@ -339,10 +486,17 @@ impl ArticlesService {
             .with(article::author::fetch())
             .with(article::tags::fetch(vec![]))
             .take(query.limit.unwrap_or(20))
-            .skip(query.offset.unwrap_or(0))
-            .order_by(article::created_at::order(Direction::Desc))
-            .exec()
-            .await?;
+            .skip(query.offset.unwrap_or(0));
+
+        let _articles = match SettingsService::feed_sort(settings.as_ref()) {
+            FeedSort::Latest => _articles,
+            FeedSort::Popular => {
+                _articles.order_by(article::favorites_count::order(Direction::Desc))
+            }
+        }
+        .order_by(article::created_at::order(Direction::Desc))
+        .exec()
+        .await?;
 
         let articles_count = prisma.article().count(filter).exec().await?;
 
@@ -368,13 +522,9 @@ impl ArticlesService {
         prisma: Prisma,
         Path(slug): Path<String>,
     ) -> Result<Json<ArticleBody<Article>>, AppError> {
-        let article = prisma
-            .article()
-            .find_unique(article::slug::equals(slug.clone()))
-            .with(article::author::fetch())
-            .exec()
-            .await?
-            .ok_or(AppError::NotFound(String::from("Article not found")))?;
+        auth_user.require_scope(Scope::FavoritesWrite)?;
+
+        let article = Self::find_visible_article(&prisma, &slug, Some(auth_user.user_id)).await?;
 
         let _ = prisma
             .user_favorite_article()
@@ -389,7 +539,7 @@ impl ArticlesService {
         let article = prisma
             .article()
             .update(
-                article::slug::equals(slug),
+                article::id::equals(article.id),
                 vec![article::favorites_count::increment(1)],
             )
             .with(article::author::fetch())
@@ -409,13 +559,9 @@ impl ArticlesService {
         prisma: Prisma,
         Path(slug): Path<String>,
     ) -> Result<Json<ArticleBody<Article>>, AppError> {
-        let article = prisma
-            .article()
-            .find_unique(article::slug::equals(slug.clone()))
-            .with(article::author::fetch())
-            .exec()
-            .await?
-            .ok_or(AppError::NotFound(String::from("Article not found")))?;
+        auth_user.require_scope(Scope::FavoritesWrite)?;
+
+        let article = Self::find_article(&prisma, &slug).await?;
 
         let _ = prisma
             .user_favorite_article()
@@ -429,7 +575,7 @@ impl ArticlesService {
         let article = prisma
             .article()
             .update(
-                article::slug::equals(slug),
+                article::id::equals(article.id),
                 vec![article::favorites_count::decrement(1)],
             )
             .with(article::author::fetch())
@@ -447,6 +593,7 @@ impl ArticlesService {
     pub async fn create_comment(
         auth_user: AuthUser,
         prisma: Prisma,
+        ctx: State<AppContext>,
         Path(slug): Path<String>,
         Json(input): Json<CommentBody<CommentCreateInput>>,
     ) -> Result<Json<CommentBody<Comment>>, AppError> {
@@ -454,13 +601,17 @@ impl ArticlesService {
             comment: CommentCreateInput { body },
         } = input;
 
-        let article = prisma
-            .article()
-            .find_unique(article::slug::equals(slug.clone()))
-            .with(article::author::fetch())
-            .exec()
-            .await?
-            .ok_or(AppError::NotFound(String::from("Article not found")))?;
+        auth_user.require_scope(Scope::CommentsWrite)?;
+
+        UsersService::check_email_verified(&prisma, &ctx, &auth_user).await?;
+
+        let article = Self::find_visible_article(&prisma, &slug, Some(auth_user.user_id)).await?;
+
+        if RelationshipsService::is_blocked(&prisma, article.author_id, auth_user.user_id).await? {
+            return Err(AppError::Forbidden(String::from(
+                "You cannot comment on this article",
+            )));
+        }
 
         let comment = prisma
             .comment()
@@ -484,20 +635,25 @@ impl ArticlesService {
         prisma: Prisma,
         Path(slug): Path<String>,
     ) -> Result<Json<CommentsBody<Comment>>, AppError> {
-        let article = prisma
-            .article()
-            .find_unique(article::slug::equals(slug.clone()))
-            .with(article::author::fetch())
-            .exec()
-            .await?
-            .ok_or(AppError::NotFound(String::from("Article not found")))?;
+        let auth_user = auth_user.with_scope(Scope::ArticlesRead);
+        let viewer_id = auth_user.0.as_ref().map(|user| user.user_id);
+        let article = Self::find_visible_article(&prisma, &slug, viewer_id).await?;
+
+        let mut filter = vec![
+            comment::article_id::equals(article.id),
+            comment::deleted_at::equals(None),
+            comment::author::is(vec![user::deleted_at::equals(None)]),
+        ];
+
+        if let Some(user) = &auth_user.0 {
+            filter.push(comment::author::is(vec![
+                RelationshipsService::not_muted_by(user.user_id),
+            ]));
+        }
 
         let comments = prisma
             .comment()
-            .find_many(vec![
-                comment::article_id::equals(article.id),
-                comment::deleted_at::equals(None),
-            ])
+            .find_many(filter)
             .with(comment::author::fetch())
             .exec()
             .await?;
@@ -522,20 +678,36 @@ impl ArticlesService {
     pub async fn delete_comment(
         auth_user: AuthUser,
         prisma: Prisma,
-        Path((_slug, comment_id)): Path<(String, i32)>,
+        client: ClientInfo,
+        Path((slug, comment_id)): Path<(String, i32)>,
     ) -> Result<Json<String>, AppError> {
+        auth_user.require_scope(Scope::CommentsWrite)?;
+
+        let article = Self::find_article(&prisma, &slug).await?;
+
         let comment = prisma
             .comment()
             .find_unique(comment::id::equals(comment_id))
             .with(comment::author::fetch())
             .exec()
             .await?
+            .filter(|comment| comment.article_id == article.id)
             .ok_or(AppError::NotFound(String::from("Comment not found")))?;
 
         if comment.author_id != auth_user.user_id {
-            return Err(AppError::BadRequest(String::from(
-                "You are not the author of this comment",
-            )));
+            if !auth_user
+                .has_permission(&prisma, Permission::ModerateComments)
+                .await?
+            {
+                return Err(AppError::Forbidden(String::from(
+                    "You are not the author of this comment",
+                )));
+            }
+
+            info!(
+                "moderator {} deleted comment {} by user {}",
+                auth_user.user_id, comment.id, comment.author_id
+            );
         }
 
         let _ = prisma
@@ -547,6 +719,16 @@ impl ArticlesService {
             .exec()
             .await?;
 
+        AuditService::record(
+            &prisma,
+            &client,
+            AuditAction::CommentDeleted,
+            Some(auth_user.user_id),
+            Some(comment.author_id).filter(|author_id| *author_id != auth_user.user_id),
+            Some(&format!("{}#{}", article.slug, comment.id)),
+        )
+        .await;
+
         Ok(Json::from("Comment deleted".to_string()))
     }
 }
@@ -0,0 +1,320 @@
+use prisma_client_rust::{prisma_errors::query_engine::UniqueKeyViolation, Direction, QueryError};
+use std::future::Future;
+use tracing::info;
+
+use crate::{
+    app_error::AppError,
+    domain::users::token::TokenService,
+    prisma::{article, article_slug, PrismaClient},
+};
+
+/// Leaves room for a suffix while keeping URLs readable.
+const MAX_LENGTH: usize = 80;
+/// Used when nothing of the title survives, such as for a title made of punctuation.
+const FALLBACK: &str = "article";
+/// Slugs that would be shadowed by other article routes.
+const RESERVED: &[&str] = &["feed"];
+/// Numbered suffixes tried before falling back to a random one.
+const MAX_NUMBERED_SUFFIX: u32 = 9;
+const RANDOM_SUFFIX_LENGTH: usize = 6;
+/// Writes tried before a slug collision is reported as a conflict.
+const MAX_WRITE_ATTEMPTS: u32 = 3;
+
+pub struct Slug;
+
+impl Slug {
+    /// Turns a title into a URL-safe slug.
+    ///
+    /// Other scripts are transliterated to ASCII first, so `Crème brûlée` becomes
+    /// `creme-brulee` and `한국어 제목` becomes `hangugeo-jemog`. Everything but letters and
+    /// digits turns into single dashes, and long slugs are cut at a word boundary.
+    pub fn generate(title: &str) -> String {
+        let ascii = deunicode::deunicode(title).to_lowercase();
+
+        let mut slug = String::with_capacity(ascii.len());
+        for c in ascii.chars() {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+
+        if slug.len() > MAX_LENGTH {
+            let cut = &slug[..MAX_LENGTH];
+            // Cuts at the last dash unless that would drop most of the slug
+            slug = match cut.rfind('-') {
+                Some(index) if index > MAX_LENGTH / 2 => cut[..index].to_string(),
+                _ => cut.to_string(),
+            };
+        }
+
+        let slug = slug.trim_end_matches('-');
+
+        if slug.is_empty() {
+            FALLBACK.to_string()
+        } else {
+            slug.to_string()
+        }
+    }
+
+    /// A slug for the title that no other article uses, now or in the past.
+    ///
+    /// Collisions get `-2` up to `-9` appended, and a short random suffix after that.
+    /// `article_id` is the article being renamed, which may keep its own slug or take back
+    /// a previous one.
+    pub async fn unique(
+        prisma: &PrismaClient,
+        title: &str,
+        article_id: Option<i32>,
+    ) -> Result<String, AppError> {
+        let base = Self::generate(title);
+
+        if !RESERVED.contains(&base.as_str()) && Self::is_free(prisma, &base, article_id).await? {
+            return Ok(base);
+        }
+
+        for number in 2..=MAX_NUMBERED_SUFFIX {
+            let candidate = format!("{}-{}", base, number);
+            if Self::is_free(prisma, &candidate, article_id).await? {
+                return Ok(candidate);
+            }
+        }
+
+        loop {
+            let candidate = format!(
+                "{}-{}",
+                base,
+                TokenService::random_string(RANDOM_SUFFIX_LENGTH).to_lowercase()
+            );
+            if Self::is_free(prisma, &candidate, article_id).await? {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    /// Runs `write` with a unique slug for the title.
+    ///
+    /// Finding a free slug and writing it are separate queries, so a concurrent article can
+    /// take the slug in between. The write is then retried with the next free slug.
+    pub async fn write_unique<T, F, Fut>(
+        prisma: &PrismaClient,
+        title: &str,
+        article_id: Option<i32>,
+        mut write: F,
+    ) -> Result<T, AppError>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<T, QueryError>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let slug = Self::unique(prisma, title, article_id).await?;
+
+            match write(slug).await {
+                Err(e)
+                    if attempt < MAX_WRITE_ATTEMPTS
+                        && e.is_prisma_error::<UniqueKeyViolation>() =>
+                {
+                    attempt += 1;
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// Regenerates the slugs of all live articles, oldest first so earlier articles keep
+    /// the unsuffixed slug. Returns the number of articles whose slug changed.
+    pub async fn reslug_all(prisma: &PrismaClient, dry_run: bool) -> Result<usize, AppError> {
+        let articles = prisma
+            .article()
+            .find_many(vec![article::deleted_at::equals(None)])
+            .order_by(article::id::order(Direction::Asc))
+            .exec()
+            .await?;
+
+        let mut changed = 0;
+
+        for data in articles {
+            if Self::matches(&data.slug, &data.title) {
+                continue;
+            }
+
+            let slug = Self::unique(prisma, &data.title, Some(data.id)).await?;
+            if slug == data.slug {
+                continue;
+            }
+
+            info!("article {}: {} -> {}", data.id, data.slug, slug);
+            changed += 1;
+
+            if !dry_run {
+                prisma
+                    .article()
+                    .update(
+                        article::id::equals(data.id),
+                        vec![article::slug::set(slug.clone())],
+                    )
+                    .exec()
+                    .await?;
+
+                Self::record_rename(prisma, data.id, &data.slug, &slug).await?;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Keeps the previous slug of a renamed article resolving to it.
+    pub async fn record_rename(
+        prisma: &PrismaClient,
+        article_id: i32,
+        previous_slug: &str,
+        slug: &str,
+    ) -> Result<(), AppError> {
+        if previous_slug == slug {
+            return Ok(());
+        }
+
+        prisma
+            .article_slug()
+            .upsert(
+                article_slug::slug::equals(previous_slug.to_string()),
+                article_slug::create(
+                    previous_slug.to_string(),
+                    article::id::equals(article_id),
+                    vec![],
+                ),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        // An article taking back an old slug no longer needs it in its history
+        prisma
+            .article_slug()
+            .delete_many(vec![article_slug::slug::equals(slug.to_string())])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Whether the slug was generated from the title, with or without a suffix.
+    fn matches(slug: &str, title: &str) -> bool {
+        let base = Self::generate(title);
+
+        match slug.strip_prefix(&base) {
+            Some("") => !RESERVED.contains(&slug),
+            Some(suffix) => suffix
+                .strip_prefix('-')
+                .is_some_and(Self::is_generated_suffix),
+            None => false,
+        }
+    }
+
+    /// Suffixes `unique` appends: a number from 2 up, or the random suffix.
+    fn is_generated_suffix(suffix: &str) -> bool {
+        let numbered = (2..=MAX_NUMBERED_SUFFIX).any(|number| number.to_string() == suffix);
+        let random = suffix.len() == RANDOM_SUFFIX_LENGTH
+            && suffix
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+
+        numbered || random
+    }
+
+    async fn is_free(
+        prisma: &PrismaClient,
+        slug: &str,
+        article_id: Option<i32>,
+    ) -> Result<bool, AppError> {
+        let existing = prisma
+            .article()
+            .find_unique(article::slug::equals(slug.to_string()))
+            .exec()
+            .await?;
+
+        if let Some(existing) = existing {
+            return Ok(Some(existing.id) == article_id);
+        }
+
+        let previous = prisma
+            .article_slug()
+            .find_unique(article_slug::slug::equals(slug.to_string()))
+            .exec()
+            .await?;
+
+        Ok(match previous {
+            Some(previous) => Some(previous.article_id) == article_id,
+            None => true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_transliterates_other_scripts() {
+        assert_eq!(Slug::generate("Crème brûlée"), "creme-brulee");
+        assert_eq!(Slug::generate("한국어 제목"), "hangugeo-jemog");
+        assert_eq!(Slug::generate("日本語のタイトル"), "ri-ben-yu-notaitoru");
+    }
+
+    #[test]
+    fn generate_collapses_separators() {
+        assert_eq!(
+            Slug::generate("How to train your dragon?"),
+            "how-to-train-your-dragon"
+        );
+        assert_eq!(Slug::generate("  --Hello,   World!-- "), "hello-world");
+        assert_eq!(Slug::generate("a/b?c#d&e"), "a-b-c-d-e");
+    }
+
+    #[test]
+    fn generate_falls_back_when_nothing_is_left() {
+        assert_eq!(Slug::generate("!!!"), FALLBACK);
+        assert_eq!(Slug::generate(""), FALLBACK);
+        assert_eq!(Slug::generate("   "), FALLBACK);
+    }
+
+    #[test]
+    fn generate_cuts_long_titles_at_a_word_boundary() {
+        let slug = Slug::generate(&"word ".repeat(40));
+        assert_eq!(slug, vec!["word"; 16].join("-"));
+
+        assert_eq!(Slug::generate(&"x".repeat(120)), "x".repeat(MAX_LENGTH));
+    }
+
+    #[test]
+    fn generate_keeps_the_reserved_word() {
+        // `unique` adds the suffix, so `matches` can still recognize it
+        assert_eq!(Slug::generate("Feed"), "feed");
+    }
+
+    #[test]
+    fn matches_generated_slugs() {
+        assert!(Slug::matches("rust", "Rust"));
+        assert!(Slug::matches("rust-2", "Rust"));
+        assert!(Slug::matches("rust-9", "Rust"));
+        assert!(Slug::matches("rust-x7k2q9", "Rust"));
+        assert!(Slug::matches("feed-2", "Feed"));
+    }
+
+    #[test]
+    fn matches_rejects_other_slugs() {
+        assert!(!Slug::matches("rust-guide", "Rust"));
+        assert!(!Slug::matches("rust-1", "Rust"));
+        assert!(!Slug::matches("rust-10", "Rust"));
+        assert!(!Slug::matches("rust-02", "Rust"));
+        assert!(!Slug::matches("rust-X7K2Q9", "Rust"));
+        assert!(!Slug::matches("rust-x7k2q9a", "Rust"));
+        assert!(!Slug::matches("rust-", "Rust"));
+        assert!(!Slug::matches("rustacean", "Rust"));
+        assert!(!Slug::matches("go", "Rust"));
+        assert!(!Slug::matches("feed", "Feed"));
+    }
+}
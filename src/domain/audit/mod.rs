@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+pub mod request;
+pub mod response;
+pub mod service;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventsBody<T> {
+    pub audit_events: Vec<T>,
+    pub audit_events_count: usize,
+}
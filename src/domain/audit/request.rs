@@ -0,0 +1,26 @@
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+
+use crate::prisma::AuditAction;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditListQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Filters of the admin query. All of them are optional and combine with AND.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditSearchQuery {
+    pub actor_id: Option<i32>,
+    pub subject_id: Option<i32>,
+    pub action: Option<AuditAction>,
+    pub ip: Option<String>,
+    pub request_id: Option<String>,
+    /// RFC 3339 timestamps bounding `createdAt`, inclusive.
+    pub since: Option<DateTime<FixedOffset>>,
+    pub until: Option<DateTime<FixedOffset>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
@@ -0,0 +1,44 @@
+use prisma_client_rust::chrono::FixedOffset;
+use serde::{Deserialize, Serialize};
+
+use crate::prisma::{audit_event, AuditAction};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub id: i32,
+    pub action: AuditAction,
+    pub actor_id: Option<i32>,
+    pub subject_id: Option<i32>,
+    pub target: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub created_at: ::prisma_client_rust::chrono::DateTime<FixedOffset>,
+}
+
+impl AuditEvent {
+    /// Drops the actor and their client details.
+    pub fn redact(&mut self) {
+        self.actor_id = None;
+        self.ip = None;
+        self.user_agent = None;
+        self.request_id = None;
+    }
+}
+
+impl From<audit_event::Data> for AuditEvent {
+    fn from(data: audit_event::Data) -> Self {
+        Self {
+            id: data.id,
+            action: data.action,
+            actor_id: data.actor_id,
+            subject_id: data.subject_id,
+            target: data.target,
+            ip: data.ip,
+            user_agent: data.user_agent,
+            request_id: data.request_id,
+            created_at: data.created_at,
+        }
+    }
+}
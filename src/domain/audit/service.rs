@@ -0,0 +1,187 @@
+use axum::{extract::Query, Extension, Json};
+use prisma_client_rust::{or, Direction};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::{
+    app_error::AppError,
+    domain::{access_tokens::scope::Scope, users::permission::Permission},
+    extractor::{AuthUser, ClientInfo},
+    prisma::{audit_event, AuditAction, PrismaClient},
+};
+
+use super::{
+    request::{AuditListQuery, AuditSearchQuery},
+    response::AuditEvent,
+    AuditEventsBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+const TARGET_MAX_LENGTH: usize = 191;
+
+pub struct AuditService;
+
+impl AuditService {
+    /// Appends an event to the audit log.
+    ///
+    /// `actor_id` is the user who acted, `subject_id` the user the event is about when that
+    /// is someone else, such as the account of a failed login. A failed write is logged
+    /// instead of failing the action that is being audited.
+    pub async fn record(
+        prisma: &PrismaClient,
+        client: &ClientInfo,
+        action: AuditAction,
+        actor_id: Option<i32>,
+        subject_id: Option<i32>,
+        target: Option<&str>,
+    ) {
+        let target = target.map(|target| target.chars().take(TARGET_MAX_LENGTH).collect());
+
+        let result = prisma
+            .audit_event()
+            .create(
+                action,
+                vec![
+                    audit_event::actor_id::set(actor_id),
+                    audit_event::subject_id::set(subject_id),
+                    audit_event::target::set(target),
+                    audit_event::ip::set(Some(client.ip.clone())),
+                    audit_event::user_agent::set(client.user_agent.clone()),
+                    audit_event::request_id::set(client.request_id.clone()),
+                ],
+            )
+            .exec()
+            .await;
+
+        if let Err(e) = result {
+            error!(
+                "failed to record audit event {:?} for user {:?}: {:?}",
+                action, actor_id, e
+            );
+        }
+    }
+
+    /// Removes what identifies the client from the events of a purged user. The events
+    /// themselves stay, so the log keeps its history.
+    pub async fn anonymize(prisma: &PrismaClient, user_id: i32) -> Result<(), AppError> {
+        prisma
+            .audit_event()
+            .update_many(
+                vec![or![
+                    audit_event::actor_id::equals(Some(user_id)),
+                    audit_event::subject_id::equals(Some(user_id)),
+                ]],
+                vec![
+                    audit_event::target::set(None),
+                    audit_event::ip::set(None),
+                    audit_event::user_agent::set(None),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Events where the current user acted or that concern them, newest first.
+    ///
+    /// Events someone else caused don't reveal who that was or where they came from.
+    pub async fn get_own_events(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        Query(query): Query<AuditListQuery>,
+    ) -> Result<Json<AuditEventsBody<AuditEvent>>, AppError> {
+        auth_user.require_scope(Scope::ProfileRead)?;
+
+        let filter = vec![or![
+            audit_event::actor_id::equals(Some(auth_user.user_id)),
+            audit_event::subject_id::equals(Some(auth_user.user_id)),
+        ]];
+
+        let Json(mut body) = Self::list(&prisma, filter, query.limit, query.offset).await?;
+        for event in &mut body.audit_events {
+            if event.actor_id != Some(auth_user.user_id) {
+                event.redact();
+            }
+        }
+
+        Ok(Json::from(body))
+    }
+
+    /// Searches the whole log. Only admins may read other users' events.
+    pub async fn search_events(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        Query(query): Query<AuditSearchQuery>,
+    ) -> Result<Json<AuditEventsBody<AuditEvent>>, AppError> {
+        auth_user.require_session()?;
+
+        if !auth_user
+            .has_permission(&prisma, Permission::ViewAuditLog)
+            .await?
+        {
+            return Err(AppError::Forbidden(String::from(
+                "You are not allowed to view the audit log",
+            )));
+        }
+
+        let mut filter = Vec::new();
+
+        if let Some(actor_id) = query.actor_id {
+            filter.push(audit_event::actor_id::equals(Some(actor_id)));
+        }
+
+        if let Some(subject_id) = query.subject_id {
+            filter.push(audit_event::subject_id::equals(Some(subject_id)));
+        }
+
+        if let Some(action) = query.action {
+            filter.push(audit_event::action::equals(action));
+        }
+
+        if let Some(ip) = query.ip {
+            filter.push(audit_event::ip::equals(Some(ip)));
+        }
+
+        if let Some(request_id) = query.request_id {
+            filter.push(audit_event::request_id::equals(Some(request_id)));
+        }
+
+        if let Some(since) = query.since {
+            filter.push(audit_event::created_at::gte(since));
+        }
+
+        if let Some(until) = query.until {
+            filter.push(audit_event::created_at::lte(until));
+        }
+
+        Self::list(&prisma, filter, query.limit, query.offset).await
+    }
+
+    async fn list(
+        prisma: &PrismaClient,
+        filter: Vec<audit_event::WhereParam>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Json<AuditEventsBody<AuditEvent>>, AppError> {
+        let audit_events = prisma
+            .audit_event()
+            .find_many(filter.clone())
+            .order_by(audit_event::id::order(Direction::Desc))
+            .take(limit.unwrap_or(20))
+            .skip(offset.unwrap_or(0))
+            .exec()
+            .await?
+            .into_iter()
+            .map(AuditEvent::from)
+            .collect();
+
+        let audit_events_count = prisma.audit_event().count(filter).exec().await?;
+
+        Ok(Json::from(AuditEventsBody {
+            audit_events,
+            audit_events_count: audit_events_count as usize,
+        }))
+    }
+}
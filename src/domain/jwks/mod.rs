@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+pub mod response;
+pub mod service;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JwksBody<T> {
+    pub keys: Vec<T>,
+}
@@ -0,0 +1,63 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use crate::config::jwt::JwtKey;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(rename = "use")]
+    pub use_: String,
+    pub alg: String,
+    pub kid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+}
+
+impl JwtKey {
+    pub fn to_jwk(&self) -> Option<Jwk> {
+        let public_pem = self.public_pem.as_deref()?;
+
+        match self.algorithm {
+            Algorithm::RS256 => {
+                use rsa::{pkcs8::DecodePublicKey as _, traits::PublicKeyParts};
+
+                let key = rsa::RsaPublicKey::from_public_key_pem(public_pem).ok()?;
+
+                Some(Jwk {
+                    kty: String::from("RSA"),
+                    use_: String::from("sig"),
+                    alg: String::from("RS256"),
+                    kid: self.kid.clone(),
+                    n: Some(URL_SAFE_NO_PAD.encode(key.n().to_bytes_be())),
+                    e: Some(URL_SAFE_NO_PAD.encode(key.e().to_bytes_be())),
+                    crv: None,
+                    x: None,
+                })
+            }
+            Algorithm::EdDSA => {
+                let key = ed25519_dalek::VerifyingKey::from_public_key_pem(public_pem).ok()?;
+
+                Some(Jwk {
+                    kty: String::from("OKP"),
+                    use_: String::from("sig"),
+                    alg: String::from("EdDSA"),
+                    kid: self.kid.clone(),
+                    n: None,
+                    e: None,
+                    crv: Some(String::from("Ed25519")),
+                    x: Some(URL_SAFE_NO_PAD.encode(key.as_bytes())),
+                })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use axum::{extract::State, Json};
+
+use crate::config::AppContext;
+
+use super::{response::Jwk, JwksBody};
+
+pub struct JwksService;
+
+impl JwksService {
+    pub async fn get_jwks(ctx: State<AppContext>) -> Json<JwksBody<Jwk>> {
+        let keys = ctx
+            .config
+            .jwt
+            .published_keys()
+            .filter_map(|key| key.to_jwk())
+            .collect();
+
+        Json::from(JwksBody { keys })
+    }
+}
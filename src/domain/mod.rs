@@ -1,4 +1,7 @@
+pub mod access_tokens;
 pub mod articles;
+pub mod audit;
+pub mod jwks;
 pub mod profiles;
 pub mod tags;
 pub mod users;
@@ -0,0 +1,171 @@
+use axum::{extract::Path, Extension, Json};
+use prisma_client_rust::Direction;
+use std::sync::Arc;
+
+use crate::{
+    app_error::AppError,
+    domain::{access_tokens::scope::Scope, users::username::Username},
+    extractor::AuthUser,
+    prisma::{follow_request, user, user_follows, PrismaClient},
+};
+
+use super::{
+    response::{FollowRequest, Profile},
+    service::ProfilesService,
+    FollowRequestsBody, ProfileBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+pub struct FollowRequestsService;
+
+impl FollowRequestsService {
+    /// Pending requests to follow the current user, oldest first.
+    pub async fn get_follow_requests(
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<FollowRequestsBody<FollowRequest>>, AppError> {
+        auth_user.require_scope(Scope::ProfileRead)?;
+
+        let follow_requests = prisma
+            .follow_request()
+            .find_many(vec![
+                follow_request::target_id::equals(auth_user.user_id),
+                follow_request::requester::is(vec![user::deleted_at::equals(None)]),
+            ])
+            .with(follow_request::requester::fetch())
+            .order_by(follow_request::created_at::order(Direction::Asc))
+            .exec()
+            .await?
+            .into_iter()
+            .map(|request| FollowRequest {
+                created_at: request.created_at,
+                profile: request.requester.unwrap().to_profile(false),
+            })
+            .collect();
+
+        Ok(Json::from(FollowRequestsBody { follow_requests }))
+    }
+
+    pub async fn approve_follow_request(
+        Path(username): Path<String>,
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
+        let requester = Self::take_request(&prisma, &auth_user, &username).await?;
+
+        Self::create_follow(&prisma, requester.id, auth_user.user_id).await?;
+
+        Ok(Json::from(ProfileBody {
+            profile: ProfilesService::profile_for(&prisma, &auth_user, requester).await?,
+        }))
+    }
+
+    pub async fn reject_follow_request(
+        Path(username): Path<String>,
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
+        let requester = Self::take_request(&prisma, &auth_user, &username).await?;
+
+        Ok(Json::from(ProfileBody {
+            profile: ProfilesService::profile_for(&prisma, &auth_user, requester).await?,
+        }))
+    }
+
+    /// Turns every pending request into a follow, for accounts that stop being private.
+    pub async fn approve_all(prisma: &PrismaClient, target_id: i32) -> Result<(), AppError> {
+        let requests = prisma
+            .follow_request()
+            .find_many(vec![follow_request::target_id::equals(target_id)])
+            .exec()
+            .await?;
+
+        for request in requests {
+            Self::create_follow(prisma, request.requester_id, target_id).await?;
+        }
+
+        prisma
+            .follow_request()
+            .delete_many(vec![follow_request::target_id::equals(target_id)])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn is_requested(
+        prisma: &PrismaClient,
+        requester_id: i32,
+        target_id: i32,
+    ) -> Result<bool, AppError> {
+        let request = prisma
+            .follow_request()
+            .find_unique(follow_request::requester_id_target_id(
+                requester_id,
+                target_id,
+            ))
+            .exec()
+            .await?;
+
+        Ok(request.is_some())
+    }
+
+    /// Removes the request sent by `username` and returns the requester.
+    async fn take_request(
+        prisma: &PrismaClient,
+        auth_user: &AuthUser,
+        username: &str,
+    ) -> Result<user::Data, AppError> {
+        let request = prisma
+            .follow_request()
+            .find_first(vec![
+                follow_request::target_id::equals(auth_user.user_id),
+                follow_request::requester::is(vec![
+                    user::canonical_username::equals(Username::canonical(username)),
+                    user::deleted_at::equals(None),
+                ]),
+            ])
+            .with(follow_request::requester::fetch())
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("Follow request not found")))?;
+
+        prisma
+            .follow_request()
+            .delete(follow_request::requester_id_target_id(
+                request.requester_id,
+                request.target_id,
+            ))
+            .exec()
+            .await?;
+
+        Ok(*request.requester.unwrap())
+    }
+
+    async fn create_follow(
+        prisma: &PrismaClient,
+        follower_id: i32,
+        followee_id: i32,
+    ) -> Result<(), AppError> {
+        prisma
+            .user_follows()
+            .upsert(
+                user_follows::following_id_followed_by_id(followee_id, follower_id),
+                user_follows::create(
+                    user::id::equals(follower_id),
+                    user::id::equals(followee_id),
+                    vec![],
+                ),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+}
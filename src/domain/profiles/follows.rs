@@ -0,0 +1,195 @@
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use prisma_client_rust::Direction;
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    app_error::AppError,
+    domain::{access_tokens::scope::Scope, users::username::Username},
+    extractor::OptionalAuthUser,
+    prisma::{follow_request, user, user_block, user_follows, user_mute, PrismaClient},
+};
+
+use super::{
+    relationship::RelationshipsService, request::ProfileListQuery, response::Profile,
+    service::ProfilesService, ProfilesBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+pub struct FollowsService;
+
+impl FollowsService {
+    pub async fn get_followers(
+        Path(username): Path<String>,
+        auth_user: OptionalAuthUser,
+        prisma: Prisma,
+        Query(query): Query<ProfileListQuery>,
+    ) -> Result<Json<ProfilesBody<Profile>>, AppError> {
+        let auth_user = auth_user.with_scope(Scope::ProfileRead);
+        let data = Self::find_visible(&prisma, &auth_user, &username).await?;
+
+        let filter = vec![
+            user_follows::following_id::equals(data.id),
+            user_follows::followed_by::is(vec![user::deleted_at::equals(None)]),
+        ];
+
+        let follows = prisma
+            .user_follows()
+            .find_many(filter.clone())
+            .with(user_follows::followed_by::fetch())
+            .order_by(user_follows::followed_by_id::order(Direction::Asc))
+            .take(query.limit.unwrap_or(20))
+            .skip(query.offset.unwrap_or(0))
+            .exec()
+            .await?;
+
+        let profiles_count = prisma.user_follows().count(filter).exec().await?;
+
+        let users = follows
+            .into_iter()
+            .filter_map(|follow| follow.followed_by.map(|data| *data))
+            .collect();
+
+        Ok(Json::from(ProfilesBody {
+            profiles: Self::to_profiles(&prisma, &auth_user, users).await?,
+            profiles_count: profiles_count as usize,
+        }))
+    }
+
+    pub async fn get_following(
+        Path(username): Path<String>,
+        auth_user: OptionalAuthUser,
+        prisma: Prisma,
+        Query(query): Query<ProfileListQuery>,
+    ) -> Result<Json<ProfilesBody<Profile>>, AppError> {
+        let auth_user = auth_user.with_scope(Scope::ProfileRead);
+        let data = Self::find_visible(&prisma, &auth_user, &username).await?;
+
+        let filter = vec![
+            user_follows::followed_by_id::equals(data.id),
+            user_follows::following::is(vec![user::deleted_at::equals(None)]),
+        ];
+
+        let follows = prisma
+            .user_follows()
+            .find_many(filter.clone())
+            .with(user_follows::following::fetch())
+            .order_by(user_follows::following_id::order(Direction::Asc))
+            .take(query.limit.unwrap_or(20))
+            .skip(query.offset.unwrap_or(0))
+            .exec()
+            .await?;
+
+        let profiles_count = prisma.user_follows().count(filter).exec().await?;
+
+        let users = follows
+            .into_iter()
+            .filter_map(|follow| follow.following.map(|data| *data))
+            .collect();
+
+        Ok(Json::from(ProfilesBody {
+            profiles: Self::to_profiles(&prisma, &auth_user, users).await?,
+            profiles_count: profiles_count as usize,
+        }))
+    }
+
+    /// Resolves the profile whose lists are requested. Private accounts only share them
+    /// with approved followers, and blocked viewers are told the user does not exist.
+    async fn find_visible(
+        prisma: &PrismaClient,
+        auth_user: &OptionalAuthUser,
+        username: &str,
+    ) -> Result<user::Data, AppError> {
+        let data = Username::resolve(prisma, username)
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        let viewer_id = auth_user.0.as_ref().map(|user| user.user_id);
+
+        if let Some(viewer_id) = viewer_id {
+            if RelationshipsService::is_blocked(prisma, data.id, viewer_id).await? {
+                return Err(AppError::NotFound(String::from("User not found")));
+            }
+        }
+
+        if !ProfilesService::can_view(prisma, &data, viewer_id).await? {
+            return Err(AppError::Forbidden(String::from("This account is private")));
+        }
+
+        Ok(data)
+    }
+
+    /// Builds the list entries, looking up the viewer's follows, follow requests, blocks and
+    /// mutes with one query each for the whole page.
+    async fn to_profiles(
+        prisma: &PrismaClient,
+        auth_user: &OptionalAuthUser,
+        users: Vec<user::Data>,
+    ) -> Result<Vec<Profile>, AppError> {
+        let ids: Vec<i32> = users.iter().map(|data| data.id).collect();
+
+        let Some(viewer) = auth_user.0.as_ref().filter(|_| !ids.is_empty()) else {
+            return Ok(users.into_iter().map(Profile::from).collect());
+        };
+
+        let followed: HashSet<i32> = prisma
+            .user_follows()
+            .find_many(vec![
+                user_follows::followed_by_id::equals(viewer.user_id),
+                user_follows::following_id::in_vec(ids.clone()),
+            ])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|follow| follow.following_id)
+            .collect();
+        let blocked: HashSet<i32> = prisma
+            .user_block()
+            .find_many(vec![
+                user_block::blocker_id::equals(viewer.user_id),
+                user_block::blocked_id::in_vec(ids.clone()),
+            ])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|block| block.blocked_id)
+            .collect();
+        let muted: HashSet<i32> = prisma
+            .user_mute()
+            .find_many(vec![
+                user_mute::muter_id::equals(viewer.user_id),
+                user_mute::muted_id::in_vec(ids.clone()),
+            ])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|mute| mute.muted_id)
+            .collect();
+        let requested: HashSet<i32> = prisma
+            .follow_request()
+            .find_many(vec![
+                follow_request::requester_id::equals(viewer.user_id),
+                follow_request::target_id::in_vec(ids),
+            ])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|request| request.target_id)
+            .collect();
+
+        Ok(users
+            .into_iter()
+            .map(|data| {
+                let user_id = data.id;
+                let mut profile = data.to_profile(followed.contains(&user_id));
+                profile.blocking = blocked.contains(&user_id);
+                profile.muted = muted.contains(&user_id);
+                profile.follow_requested = requested.contains(&user_id);
+                profile
+            })
+            .collect())
+    }
+}
@@ -1,9 +1,27 @@
 use serde::{Deserialize, Serialize};
 
+pub mod follow_request;
+pub mod follows;
+pub mod relationship;
+pub mod request;
 pub mod response;
 pub mod service;
+pub mod suggestion;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProfileBody<T> {
     pub profile: T,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesBody<T> {
+    pub profiles: Vec<T>,
+    pub profiles_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowRequestsBody<T> {
+    pub follow_requests: Vec<T>,
+}
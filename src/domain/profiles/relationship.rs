@@ -0,0 +1,217 @@
+use axum::{extract::Path, Extension, Json};
+use std::sync::Arc;
+
+use crate::{
+    app_error::AppError,
+    domain::{access_tokens::scope::Scope, users::username::Username},
+    extractor::AuthUser,
+    prisma::{follow_request, user, user_block, user_follows, user_mute, PrismaClient},
+};
+
+use super::{response::Profile, service::ProfilesService, ProfileBody};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+pub struct RelationshipsService;
+
+impl RelationshipsService {
+    /// Blocks a user, which also ends follows and follow requests between the two in both
+    /// directions.
+    pub async fn block_profile(
+        Path(username): Path<String>,
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
+        let target = Self::find_target(&prisma, &auth_user, &username, "block").await?;
+
+        prisma
+            .user_block()
+            .upsert(
+                user_block::blocker_id_blocked_id(auth_user.user_id, target.id),
+                user_block::create(
+                    user::id::equals(auth_user.user_id),
+                    user::id::equals(target.id),
+                    vec![],
+                ),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        prisma
+            .user_follows()
+            .delete_many(vec![
+                user_follows::followed_by_id::equals(auth_user.user_id),
+                user_follows::following_id::equals(target.id),
+            ])
+            .exec()
+            .await?;
+
+        prisma
+            .user_follows()
+            .delete_many(vec![
+                user_follows::followed_by_id::equals(target.id),
+                user_follows::following_id::equals(auth_user.user_id),
+            ])
+            .exec()
+            .await?;
+
+        prisma
+            .follow_request()
+            .delete_many(vec![
+                follow_request::requester_id::equals(target.id),
+                follow_request::target_id::equals(auth_user.user_id),
+            ])
+            .exec()
+            .await?;
+
+        prisma
+            .follow_request()
+            .delete_many(vec![
+                follow_request::requester_id::equals(auth_user.user_id),
+                follow_request::target_id::equals(target.id),
+            ])
+            .exec()
+            .await?;
+
+        Ok(Json::from(ProfileBody {
+            profile: ProfilesService::profile_for(&prisma, &auth_user, target).await?,
+        }))
+    }
+
+    pub async fn unblock_profile(
+        Path(username): Path<String>,
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
+        let target = Self::find_target(&prisma, &auth_user, &username, "unblock").await?;
+
+        prisma
+            .user_block()
+            .delete_many(vec![
+                user_block::blocker_id::equals(auth_user.user_id),
+                user_block::blocked_id::equals(target.id),
+            ])
+            .exec()
+            .await?;
+
+        Ok(Json::from(ProfileBody {
+            profile: ProfilesService::profile_for(&prisma, &auth_user, target).await?,
+        }))
+    }
+
+    /// Hides a user's articles and comments from your lists without them noticing.
+    pub async fn mute_profile(
+        Path(username): Path<String>,
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
+        let target = Self::find_target(&prisma, &auth_user, &username, "mute").await?;
+
+        prisma
+            .user_mute()
+            .upsert(
+                user_mute::muter_id_muted_id(auth_user.user_id, target.id),
+                user_mute::create(
+                    user::id::equals(auth_user.user_id),
+                    user::id::equals(target.id),
+                    vec![],
+                ),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Ok(Json::from(ProfileBody {
+            profile: ProfilesService::profile_for(&prisma, &auth_user, target).await?,
+        }))
+    }
+
+    pub async fn unmute_profile(
+        Path(username): Path<String>,
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
+        let target = Self::find_target(&prisma, &auth_user, &username, "unmute").await?;
+
+        prisma
+            .user_mute()
+            .delete_many(vec![
+                user_mute::muter_id::equals(auth_user.user_id),
+                user_mute::muted_id::equals(target.id),
+            ])
+            .exec()
+            .await?;
+
+        Ok(Json::from(ProfileBody {
+            profile: ProfilesService::profile_for(&prisma, &auth_user, target).await?,
+        }))
+    }
+
+    pub async fn is_blocked(
+        prisma: &PrismaClient,
+        blocker_id: i32,
+        blocked_id: i32,
+    ) -> Result<bool, AppError> {
+        let block = prisma
+            .user_block()
+            .find_unique(user_block::blocker_id_blocked_id(blocker_id, blocked_id))
+            .exec()
+            .await?;
+
+        Ok(block.is_some())
+    }
+
+    pub async fn is_muted(
+        prisma: &PrismaClient,
+        muter_id: i32,
+        muted_id: i32,
+    ) -> Result<bool, AppError> {
+        let mute = prisma
+            .user_mute()
+            .find_unique(user_mute::muter_id_muted_id(muter_id, muted_id))
+            .exec()
+            .await?;
+
+        Ok(mute.is_some())
+    }
+
+    /// Matches users the viewer has not muted, for filtering authors out of lists.
+    pub fn not_muted_by(viewer_id: i32) -> user::WhereParam {
+        user::muted_by::none(vec![user_mute::muter_id::equals(viewer_id)])
+    }
+
+    async fn find_target(
+        prisma: &PrismaClient,
+        auth_user: &AuthUser,
+        username: &str,
+        action: &str,
+    ) -> Result<user::Data, AppError> {
+        let target = prisma
+            .user()
+            .find_first(vec![
+                user::canonical_username::equals(Username::canonical(username)),
+                user::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("Profile not found")))?;
+
+        if target.id == auth_user.user_id {
+            return Err(AppError::BadRequest(format!(
+                "You cannot {} yourself",
+                action
+            )));
+        }
+
+        Ok(target)
+    }
+}
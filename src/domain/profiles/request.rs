@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ProfileListQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
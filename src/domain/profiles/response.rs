@@ -1,13 +1,26 @@
+use prisma_client_rust::chrono::FixedOffset;
 use serde::{Deserialize, Serialize};
 
 use crate::prisma::user;
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Profile {
     pub username: String,
     pub bio: Option<String>,
     pub image: Option<String>,
     pub following: bool,
+    pub blocking: bool,
+    pub muted: bool,
+    /// A follow request to this private profile is waiting for approval.
+    pub follow_requested: bool,
+    /// Only filled in when a single profile is returned, list entries leave them out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub followers_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub following_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub articles_count: Option<i64>,
 }
 
 impl user::Data {
@@ -17,6 +30,12 @@ impl user::Data {
             bio: self.bio,
             image: self.image,
             following,
+            blocking: false,
+            muted: false,
+            follow_requested: false,
+            followers_count: None,
+            following_count: None,
+            articles_count: None,
         }
     }
 }
@@ -28,6 +47,19 @@ impl From<user::Data> for Profile {
             bio: data.bio,
             image: data.image,
             following: false,
+            blocking: false,
+            muted: false,
+            follow_requested: false,
+            followers_count: None,
+            following_count: None,
+            articles_count: None,
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowRequest {
+    pub profile: Profile,
+    pub created_at: ::prisma_client_rust::chrono::DateTime<FixedOffset>,
+}
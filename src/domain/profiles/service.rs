@@ -1,50 +1,78 @@
-use axum::{extract::Path, Extension, Json};
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use prisma_client_rust::or;
 use std::sync::Arc;
 
 use crate::{
     app_error::AppError,
-    extractor::{AuthUser, OptionalAuthUser},
-    prisma::{user, user_follows, PrismaClient},
+    domain::{
+        access_tokens::scope::Scope, audit::service::AuditService, users::username::Username,
+    },
+    extractor::{AuthUser, ClientInfo, OptionalAuthUser},
+    prisma::{article, follow_request, user, user_follows, AuditAction, PrismaClient},
 };
 
-use super::{response::Profile, ProfileBody};
+use super::{
+    follow_request::FollowRequestsService, relationship::RelationshipsService, response::Profile,
+    ProfileBody,
+};
 
 type Prisma = Extension<Arc<PrismaClient>>;
 
 pub struct ProfilesService;
 
 impl ProfilesService {
+    /// Old usernames answer with a permanent redirect to the renamed profile.
     pub async fn get_profile(
         Path(username): Path<String>,
         auth_user: OptionalAuthUser,
         prisma: Prisma,
-    ) -> Result<Json<ProfileBody<Profile>>, AppError> {
-        let user = prisma
-            .user()
-            .find_unique(user::username::equals(username))
-            .exec()
+    ) -> Result<Response, AppError> {
+        let auth_user = auth_user.with_scope(Scope::ProfileRead);
+        let user = Username::resolve(&prisma, &username)
             .await?
             .ok_or(AppError::NotFound(String::from("User not found")))?;
 
-        return match auth_user.0 {
-            Some(auth_user) => {
-                Ok(Json::from(ProfileBody {
-                    profile: user.clone().to_profile(
-                        Self::check_following(&prisma, &auth_user, user.id).await?
-                    ),
-                }))
+        // Blocked users get the same answer as for a name that does not exist
+        if let Some(auth_user) = &auth_user.0 {
+            if RelationshipsService::is_blocked(&prisma, user.id, auth_user.user_id).await? {
+                return Err(AppError::NotFound(String::from("User not found")));
             }
-            None => Ok(Json::from(ProfileBody {
-                profile: user.to_profile(false),
-            }))
         }
+
+        if user.canonical_username != Username::canonical(&username) {
+            return Ok((
+                StatusCode::MOVED_PERMANENTLY,
+                [(header::LOCATION, format!("/api/profiles/{}", user.username))],
+            )
+                .into_response());
+        }
+
+        let profile = match auth_user.0 {
+            Some(auth_user) => Self::profile_for(&prisma, &auth_user, user).await?,
+            None => {
+                let user_id = user.id;
+                let mut profile = user.to_profile(false);
+                Self::add_counts(&prisma, &mut profile, user_id).await?;
+                profile
+            }
+        };
+
+        Ok(Json::from(ProfileBody { profile }).into_response())
     }
 
     pub async fn follow_profile(
         Path(username): Path<String>,
         auth_user: AuthUser,
         prisma: Prisma,
+        client: ClientInfo,
     ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
         let current_user = prisma
             .user()
             .find_unique(user::id::equals(auth_user.user_id))
@@ -52,7 +80,7 @@ impl ProfilesService {
             .await?
             .ok_or(AppError::NotFound(String::from("User not found")))?;
 
-        if current_user.username == username {
+        if current_user.canonical_username == Username::canonical(&username) {
             return Err(AppError::BadRequest(String::from(
                 "You cannot follow yourself",
             )));
@@ -60,27 +88,67 @@ impl ProfilesService {
 
         let followee = prisma
             .user()
-            .find_unique(user::username::equals(username))
+            .find_first(vec![
+                user::canonical_username::equals(Username::canonical(&username)),
+                user::deleted_at::equals(None),
+            ])
             .exec()
             .await?
             .ok_or(AppError::NotFound(String::from("Profile not found")))?;
 
-        prisma
-            .user_follows()
-            .upsert(
-                user_follows::following_id_followed_by_id(followee.id, current_user.id),
-                user_follows::create(
-                    user::id::equals(current_user.id),
-                    user::id::equals(followee.id),
+        if RelationshipsService::is_blocked(&prisma, followee.id, current_user.id).await? {
+            return Err(AppError::NotFound(String::from("Profile not found")));
+        }
+
+        if RelationshipsService::is_blocked(&prisma, current_user.id, followee.id).await? {
+            return Err(AppError::BadRequest(String::from(
+                "You cannot follow a user you have blocked",
+            )));
+        }
+
+        // Private accounts approve their followers, so only a request is recorded
+        if followee.private && !Self::check_following(&prisma, &auth_user, followee.id).await? {
+            prisma
+                .follow_request()
+                .upsert(
+                    follow_request::requester_id_target_id(current_user.id, followee.id),
+                    follow_request::create(
+                        user::id::equals(current_user.id),
+                        user::id::equals(followee.id),
+                        vec![],
+                    ),
+                    vec![],
+                )
+                .exec()
+                .await?;
+        } else {
+            prisma
+                .user_follows()
+                .upsert(
+                    user_follows::following_id_followed_by_id(followee.id, current_user.id),
+                    user_follows::create(
+                        user::id::equals(current_user.id),
+                        user::id::equals(followee.id),
+                        vec![],
+                    ),
                     vec![],
-                ),
-                vec![],
+                )
+                .exec()
+                .await?;
+
+            AuditService::record(
+                &prisma,
+                &client,
+                AuditAction::UserFollowed,
+                Some(current_user.id),
+                Some(followee.id),
+                Some(&followee.username),
             )
-            .exec()
-            .await?;
+            .await;
+        }
 
         Ok(Json::from(ProfileBody {
-            profile: followee.to_profile(true),
+            profile: Self::profile_for(&prisma, &auth_user, followee).await?,
         }))
     }
 
@@ -88,7 +156,10 @@ impl ProfilesService {
         Path(username): Path<String>,
         auth_user: AuthUser,
         prisma: Prisma,
+        client: ClientInfo,
     ) -> Result<Json<ProfileBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::FollowsWrite)?;
+
         let current_user = prisma
             .user()
             .find_unique(user::id::equals(auth_user.user_id))
@@ -96,7 +167,7 @@ impl ProfilesService {
             .await?
             .ok_or(AppError::NotFound(String::from("User not found")))?;
 
-        if current_user.username == username {
+        if current_user.canonical_username == Username::canonical(&username) {
             return Err(AppError::BadRequest(String::from(
                 "You cannot unfollow yourself",
             )));
@@ -104,12 +175,15 @@ impl ProfilesService {
 
         let followee = prisma
             .user()
-            .find_unique(user::username::equals(username))
+            .find_first(vec![
+                user::canonical_username::equals(Username::canonical(&username)),
+                user::deleted_at::equals(None),
+            ])
             .exec()
             .await?
             .ok_or(AppError::NotFound(String::from("Profile not found")))?;
 
-        let _ = prisma
+        let unfollowed = prisma
             .user_follows()
             .delete(user_follows::following_id_followed_by_id(
                 followee.id,
@@ -119,11 +193,136 @@ impl ProfilesService {
             .await
             .is_ok();
 
+        if unfollowed {
+            AuditService::record(
+                &prisma,
+                &client,
+                AuditAction::UserUnfollowed,
+                Some(current_user.id),
+                Some(followee.id),
+                Some(&followee.username),
+            )
+            .await;
+        }
+
+        // Also withdraws a request that is still pending
+        prisma
+            .follow_request()
+            .delete_many(vec![
+                follow_request::requester_id::equals(current_user.id),
+                follow_request::target_id::equals(followee.id),
+            ])
+            .exec()
+            .await?;
+
         Ok(Json::from(ProfileBody {
-            profile: followee.to_profile(false),
+            profile: Self::profile_for(&prisma, &auth_user, followee).await?,
         }))
     }
 
+    /// Profile with the viewer's follow, follow request, block and mute state.
+    pub(crate) async fn profile_for(
+        prisma: &Prisma,
+        auth_user: &AuthUser,
+        data: user::Data,
+    ) -> Result<Profile, AppError> {
+        let following = Self::check_following(prisma, auth_user, data.id).await?;
+        let blocking = RelationshipsService::is_blocked(prisma, auth_user.user_id, data.id).await?;
+        let muted = RelationshipsService::is_muted(prisma, auth_user.user_id, data.id).await?;
+        let follow_requested =
+            FollowRequestsService::is_requested(prisma, auth_user.user_id, data.id).await?;
+
+        let user_id = data.id;
+        let mut profile = data.to_profile(following);
+        profile.blocking = blocking;
+        profile.muted = muted;
+        profile.follow_requested = follow_requested;
+        Self::add_counts(prisma, &mut profile, user_id).await?;
+
+        Ok(profile)
+    }
+
+    /// Fills in the follower, following and article counts, leaving out deleted accounts.
+    async fn add_counts(
+        prisma: &PrismaClient,
+        profile: &mut Profile,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        let followers_count = prisma
+            .user_follows()
+            .count(vec![
+                user_follows::following_id::equals(user_id),
+                user_follows::followed_by::is(vec![user::deleted_at::equals(None)]),
+            ])
+            .exec()
+            .await?;
+
+        let following_count = prisma
+            .user_follows()
+            .count(vec![
+                user_follows::followed_by_id::equals(user_id),
+                user_follows::following::is(vec![user::deleted_at::equals(None)]),
+            ])
+            .exec()
+            .await?;
+
+        let articles_count = prisma
+            .article()
+            .count(vec![
+                article::author_id::equals(user_id),
+                article::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?;
+
+        profile.followers_count = Some(followers_count);
+        profile.following_count = Some(following_count);
+        profile.articles_count = Some(articles_count);
+
+        Ok(())
+    }
+
+    /// Private accounts are only visible to themselves and their approved followers.
+    pub async fn can_view(
+        prisma: &PrismaClient,
+        owner: &user::Data,
+        viewer_id: Option<i32>,
+    ) -> Result<bool, AppError> {
+        if !owner.private {
+            return Ok(true);
+        }
+
+        let Some(viewer_id) = viewer_id else {
+            return Ok(false);
+        };
+
+        if viewer_id == owner.id {
+            return Ok(true);
+        }
+
+        let follow = prisma
+            .user_follows()
+            .find_unique(user_follows::following_id_followed_by_id(
+                owner.id, viewer_id,
+            ))
+            .exec()
+            .await?;
+
+        Ok(follow.is_some())
+    }
+
+    /// Filter counterpart of `can_view`, matching users whose content the viewer may see.
+    pub fn visible_to(viewer_id: Option<i32>) -> user::WhereParam {
+        match viewer_id {
+            Some(viewer_id) => or![
+                user::private::equals(false),
+                user::id::equals(viewer_id),
+                user::followed_by::some(vec![user_follows::followed_by_id::equals(viewer_id)]),
+            ],
+            None => user::private::equals(false),
+        }
+    }
+
     pub async fn check_following(
         prisma: &Prisma,
         auth_user: &AuthUser,
@@ -0,0 +1,267 @@
+use axum::{extract::Query, Extension, Json};
+use prisma_client_rust::Direction;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    app_error::AppError,
+    domain::access_tokens::scope::Scope,
+    extractor::AuthUser,
+    prisma::{
+        article, article_tag, follow_request, user, user_block, user_favorite_article,
+        user_follows, user_mute, PrismaClient,
+    },
+};
+
+use super::{request::ProfileListQuery, response::Profile, ProfilesBody};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+/// Score for every followed user who follows the candidate.
+const FOLLOWED_BY_FOLLOWS_WEIGHT: i64 = 3;
+/// Score for every article of the candidate the caller favorited.
+const FAVORITED_AUTHOR_WEIGHT: i64 = 2;
+/// Score for every tag of the caller's favorites the candidate has written about.
+const SHARED_TAG_WEIGHT: i64 = 1;
+/// Caps the articles scanned for shared tags, newest first.
+const MAX_TAGGED_ARTICLES: i64 = 500;
+/// Caps the follows of followed users that are counted.
+const MAX_SCANNED_FOLLOWS: i64 = 1000;
+/// Below this many ranked candidates, recently active authors fill up the list.
+const MIN_SUGGESTIONS: usize = 20;
+/// Caps the articles scanned for recently active authors, newest first.
+const MAX_RECENT_ARTICLES: i64 = 200;
+
+pub struct SuggestionsService;
+
+impl SuggestionsService {
+    /// Users worth following, ranked by follows of the people the caller follows, authors of
+    /// their favorites and tags they share with those favorites.
+    ///
+    /// New users have none of these, so recently active authors follow the ranked ones.
+    pub async fn get_suggestions(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        Query(query): Query<ProfileListQuery>,
+    ) -> Result<Json<ProfilesBody<Profile>>, AppError> {
+        auth_user.require_scope(Scope::ProfileRead)?;
+
+        let user_id = auth_user.user_id;
+        let mut scores: HashMap<i32, i64> = HashMap::new();
+
+        let followed_ids: Vec<i32> = prisma
+            .user_follows()
+            .find_many(vec![user_follows::followed_by_id::equals(user_id)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|follow| follow.following_id)
+            .collect();
+
+        let mut excluded = Self::excluded_ids(&prisma, user_id, &followed_ids).await?;
+
+        if !followed_ids.is_empty() {
+            let follows_of_follows = prisma
+                .user_follows()
+                .find_many(vec![
+                    user_follows::followed_by_id::in_vec(followed_ids),
+                    user_follows::following_id::not_in_vec(excluded.iter().copied().collect()),
+                ])
+                .take(MAX_SCANNED_FOLLOWS)
+                .exec()
+                .await?;
+
+            for follow in follows_of_follows {
+                *scores.entry(follow.following_id).or_default() += FOLLOWED_BY_FOLLOWS_WEIGHT;
+            }
+        }
+
+        let favorites = prisma
+            .user_favorite_article()
+            .find_many(vec![
+                user_favorite_article::user_id::equals(user_id),
+                user_favorite_article::article::is(vec![article::deleted_at::equals(None)]),
+            ])
+            .with(user_favorite_article::article::fetch())
+            .exec()
+            .await?;
+
+        let favorite_ids: Vec<i32> = favorites
+            .iter()
+            .map(|favorite| favorite.article_id)
+            .collect();
+
+        for favorite in &favorites {
+            if let Some(article) = &favorite.article {
+                *scores.entry(article.author_id).or_default() += FAVORITED_AUTHOR_WEIGHT;
+            }
+        }
+
+        if !favorite_ids.is_empty() {
+            let tags: Vec<String> = prisma
+                .article_tag()
+                .find_many(vec![article_tag::article_id::in_vec(favorite_ids)])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|tag| tag.tag)
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+
+            if !tags.is_empty() {
+                let tagged = prisma
+                    .article()
+                    .find_many(vec![
+                        article::deleted_at::equals(None),
+                        article::tags::some(vec![article_tag::tag::in_vec(tags.clone())]),
+                    ])
+                    .with(article::tags::fetch(vec![article_tag::tag::in_vec(tags)]))
+                    .order_by(article::created_at::order(Direction::Desc))
+                    .take(MAX_TAGGED_ARTICLES)
+                    .exec()
+                    .await?;
+
+                // Each author counts a shared tag once, however many articles use it
+                let mut author_tags: HashSet<(i32, String)> = HashSet::new();
+                for article in tagged {
+                    for tag in article.tags.unwrap_or_default() {
+                        author_tags.insert((article.author_id, tag.tag));
+                    }
+                }
+
+                for (author_id, _) in author_tags {
+                    *scores.entry(author_id).or_default() += SHARED_TAG_WEIGHT;
+                }
+            }
+        }
+
+        scores.retain(|id, _| !excluded.contains(id));
+
+        let candidates = prisma
+            .user()
+            .find_many(vec![
+                user::id::in_vec(scores.keys().copied().collect()),
+                user::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?;
+
+        let mut ranked: Vec<(i64, user::Data)> = candidates
+            .into_iter()
+            .map(|data| (scores.get(&data.id).copied().unwrap_or_default(), data))
+            .collect();
+        // Ties go to the older account, so the order stays stable between pages
+        ranked.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.id.cmp(&b.id)));
+        let mut ranked: Vec<user::Data> = ranked.into_iter().map(|(_, data)| data).collect();
+
+        if ranked.len() < MIN_SUGGESTIONS {
+            excluded.extend(ranked.iter().map(|data| data.id));
+            ranked.extend(Self::recently_active_authors(&prisma, &excluded).await?);
+        }
+
+        let profiles_count = ranked.len();
+        let profiles = ranked
+            .into_iter()
+            .skip(query.offset.unwrap_or(0).max(0) as usize)
+            .take(query.limit.unwrap_or(20).max(0) as usize)
+            .map(|data| data.to_profile(false))
+            .collect();
+
+        Ok(Json::from(ProfilesBody {
+            profiles,
+            profiles_count,
+        }))
+    }
+
+    /// Authors of the newest articles, most recent first, leaving out `excluded` users.
+    async fn recently_active_authors(
+        prisma: &PrismaClient,
+        excluded: &HashSet<i32>,
+    ) -> Result<Vec<user::Data>, AppError> {
+        let articles = prisma
+            .article()
+            .find_many(vec![
+                article::deleted_at::equals(None),
+                article::author_id::not_in_vec(excluded.iter().copied().collect()),
+                article::author::is(vec![user::deleted_at::equals(None)]),
+            ])
+            .with(article::author::fetch())
+            .order_by(article::created_at::order(Direction::Desc))
+            .take(MAX_RECENT_ARTICLES)
+            .exec()
+            .await?;
+
+        let mut seen = HashSet::new();
+        Ok(articles
+            .into_iter()
+            .filter_map(|article| article.author.map(|author| *author))
+            .filter(|author| seen.insert(author.id))
+            .collect())
+    }
+
+    /// The caller, users they already follow or asked to follow, and anyone blocked or
+    /// muted in either direction.
+    async fn excluded_ids(
+        prisma: &PrismaClient,
+        user_id: i32,
+        followed_ids: &[i32],
+    ) -> Result<HashSet<i32>, AppError> {
+        let mut excluded = HashSet::from([user_id]);
+        excluded.extend(followed_ids);
+
+        excluded.extend(
+            prisma
+                .follow_request()
+                .find_many(vec![follow_request::requester_id::equals(user_id)])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|request| request.target_id),
+        );
+
+        excluded.extend(
+            prisma
+                .user_block()
+                .find_many(vec![user_block::blocker_id::equals(user_id)])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|block| block.blocked_id),
+        );
+
+        excluded.extend(
+            prisma
+                .user_block()
+                .find_many(vec![user_block::blocked_id::equals(user_id)])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|block| block.blocker_id),
+        );
+
+        excluded.extend(
+            prisma
+                .user_mute()
+                .find_many(vec![user_mute::muter_id::equals(user_id)])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|mute| mute.muted_id),
+        );
+
+        excluded.extend(
+            prisma
+                .user_mute()
+                .find_many(vec![user_mute::muted_id::equals(user_id)])
+                .exec()
+                .await?
+                .into_iter()
+                .map(|mute| mute.muter_id),
+        );
+
+        Ok(excluded)
+    }
+}
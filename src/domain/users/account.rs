@@ -0,0 +1,393 @@
+use axum::{extract::State, Extension, Json};
+use prisma_client_rust::{chrono, Direction};
+use std::{sync::Arc, time::Duration};
+use tracing::{error, info};
+
+use crate::{
+    app_error::AppError,
+    config::{app_config::AppConfig, AppContext},
+    domain::audit::service::AuditService,
+    extractor::AuthUser,
+    prisma::{
+        article, article_slug, article_tag, comment, email_verification_token, follow_request,
+        oidc_identity, password_reset_token, recovery_code, session, user, user_block,
+        user_favorite_article, user_follows, user_mute, user_settings, username_history,
+        PrismaClient,
+    },
+};
+
+use super::{
+    avatar::AvatarService, password::PasswordHashing, request::DeleteAccountInput,
+    token::TokenService, UserBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Deleted accounts loaded per query during the purge.
+const PURGE_PAGE_SIZE: i64 = 100;
+const ANONYMIZED_EMAIL_DOMAIN: &str = "deleted.invalid";
+/// How recent the login of a passwordless account has to be to delete it or turn off 2FA.
+const REAUTHENTICATION_WINDOW_IN_SEC: i64 = 600;
+
+pub struct AccountService;
+
+impl AccountService {
+    /// Soft-deletes the account after the password has been confirmed. Accounts without a
+    /// password, such as those created through OIDC, need a session from a recent login.
+    ///
+    /// The profile is anonymized and signed out right away. Username and email stay
+    /// reserved for the retention window, and the row is purged later.
+    pub async fn delete_account(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        Json(input): Json<UserBody<DeleteAccountInput>>,
+    ) -> Result<Json<String>, AppError> {
+        let UserBody {
+            user: DeleteAccountInput { password },
+        } = input;
+
+        let session_id = auth_user.require_session()?;
+
+        let data = prisma
+            .user()
+            .find_first(vec![
+                user::id::equals(auth_user.user_id),
+                user::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        match data.password.as_deref() {
+            Some(password_hash) => {
+                let verified = match &password {
+                    Some(password) => PasswordHashing::verify(&ctx, password, password_hash)
+                        .await
+                        .is_match(),
+                    None => false,
+                };
+
+                if !verified {
+                    return Err(AppError::Unauthorized(String::from("Invalid password")));
+                }
+            }
+            None => {
+                if !Self::is_recent_login(&prisma, session_id).await? {
+                    return Err(AppError::Unauthorized(String::from(
+                        "Log in again to delete your account",
+                    )));
+                }
+            }
+        }
+
+        prisma
+            .user()
+            .update(
+                user::id::equals(data.id),
+                vec![
+                    user::deleted_at::set(Some(chrono::Utc::now().into())),
+                    user::bio::set(None),
+                    user::image::set(None),
+                    user::avatar_key::set(None),
+                    user::pending_email::set(None),
+                    user::password::set(None),
+                    user::totp_secret::set(None),
+                    user::totp_enabled::set(false),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Self::detach(&prisma, data.id).await?;
+        AvatarService::remove_files(&ctx, data.avatar_key.as_deref()).await;
+
+        info!("user {} deleted their account", data.id);
+
+        Ok(Json::from("Account deleted".to_string()))
+    }
+
+    /// Whether the session was started within the re-authentication window. Refreshing
+    /// tokens keeps the session, so only an actual login counts.
+    pub(crate) async fn is_recent_login(
+        prisma: &PrismaClient,
+        session_id: &str,
+    ) -> Result<bool, AppError> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::seconds(REAUTHENTICATION_WINDOW_IN_SEC);
+
+        let recent = prisma
+            .session()
+            .find_first(vec![
+                session::id::equals(session_id.to_string()),
+                session::created_at::gte(cutoff.into()),
+            ])
+            .exec()
+            .await?;
+
+        Ok(recent.is_some())
+    }
+
+    /// Runs the purge every hour for as long as the server is up.
+    pub async fn run_purge(prisma: Arc<PrismaClient>, config: Arc<AppConfig>) {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = Self::purge(&prisma, &config).await {
+                error!("failed to purge deleted accounts: {:?}", e);
+            }
+        }
+    }
+
+    /// Frees identifiers of accounts past the retention window and hard-deletes
+    /// accounts past the purge window.
+    pub async fn purge(prisma: &PrismaClient, config: &AppConfig) -> Result<(), AppError> {
+        let now = chrono::Utc::now();
+        let retention_cutoff = now - chrono::Duration::days(config.auth.account_retention_days);
+        let purge_cutoff = now - chrono::Duration::days(config.auth.account_purge_days);
+
+        let mut cursor = 0;
+
+        loop {
+            let expired = prisma
+                .user()
+                .find_many(vec![
+                    user::deleted_at::lt(retention_cutoff.into()),
+                    user::id::gt(cursor),
+                ])
+                .order_by(user::id::order(Direction::Asc))
+                .take(PURGE_PAGE_SIZE)
+                .exec()
+                .await?;
+
+            let Some(last) = expired.last() else {
+                return Ok(());
+            };
+            cursor = last.id;
+
+            // One account that can't be purged must not hold up all the ones after it
+            for data in expired {
+                let user_id = data.id;
+
+                if let Err(e) = Self::purge_user(prisma, data, purge_cutoff).await {
+                    error!("failed to purge deleted user {}: {:?}", user_id, e);
+                }
+            }
+        }
+    }
+
+    async fn purge_user(
+        prisma: &PrismaClient,
+        data: user::Data,
+        purge_cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), AppError> {
+        if data
+            .deleted_at
+            .is_some_and(|deleted_at| deleted_at < purge_cutoff)
+        {
+            Self::hard_delete(prisma, data.id).await?;
+            info!("purged deleted user {}", data.id);
+        } else if !data.email.ends_with(ANONYMIZED_EMAIL_DOMAIN) {
+            prisma
+                .user()
+                .update(
+                    user::id::equals(data.id),
+                    vec![
+                        user::username::set(format!("deleted-{}", data.id)),
+                        user::canonical_username::set(format!("deleted-{}", data.id)),
+                        user::email::set(format!(
+                            "deleted-{}@{}",
+                            data.id, ANONYMIZED_EMAIL_DOMAIN
+                        )),
+                    ],
+                )
+                .exec()
+                .await?;
+            info!("released username and email of deleted user {}", data.id);
+        }
+
+        Ok(())
+    }
+
+    /// Signs the user out everywhere and drops their follows, blocks, mutes and favorites.
+    async fn detach(prisma: &PrismaClient, user_id: i32) -> Result<(), AppError> {
+        TokenService::revoke_all_sessions(prisma, user_id).await?;
+        TokenService::revoke_access_tokens(prisma, user_id).await?;
+
+        prisma
+            .recovery_code()
+            .delete_many(vec![recovery_code::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user_follows()
+            .delete_many(vec![user_follows::followed_by_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user_follows()
+            .delete_many(vec![user_follows::following_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user_block()
+            .delete_many(vec![user_block::blocker_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user_block()
+            .delete_many(vec![user_block::blocked_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user_mute()
+            .delete_many(vec![user_mute::muter_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user_mute()
+            .delete_many(vec![user_mute::muted_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .follow_request()
+            .delete_many(vec![follow_request::requester_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .follow_request()
+            .delete_many(vec![follow_request::target_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        Self::remove_favorites(prisma, user_id).await
+    }
+
+    async fn remove_favorites(prisma: &PrismaClient, user_id: i32) -> Result<(), AppError> {
+        let favorites = prisma
+            .user_favorite_article()
+            .find_many(vec![user_favorite_article::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        for favorite in favorites {
+            prisma
+                .article()
+                .update(
+                    article::id::equals(favorite.article_id),
+                    vec![article::favorites_count::decrement(1)],
+                )
+                .exec()
+                .await?;
+        }
+
+        prisma
+            .user_favorite_article()
+            .delete_many(vec![user_favorite_article::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes the user with everything that references it, children first.
+    async fn hard_delete(prisma: &PrismaClient, user_id: i32) -> Result<(), AppError> {
+        let article_ids: Vec<i32> = prisma
+            .article()
+            .find_many(vec![article::author_id::equals(user_id)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|article| article.id)
+            .collect();
+
+        prisma
+            .article_tag()
+            .delete_many(vec![article_tag::article_id::in_vec(article_ids.clone())])
+            .exec()
+            .await?;
+
+        prisma
+            .article_slug()
+            .delete_many(vec![article_slug::article_id::in_vec(article_ids.clone())])
+            .exec()
+            .await?;
+
+        prisma
+            .user_favorite_article()
+            .delete_many(vec![user_favorite_article::article_id::in_vec(
+                article_ids.clone(),
+            )])
+            .exec()
+            .await?;
+
+        prisma
+            .comment()
+            .delete_many(vec![comment::article_id::in_vec(article_ids)])
+            .exec()
+            .await?;
+
+        prisma
+            .comment()
+            .delete_many(vec![comment::author_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .article()
+            .delete_many(vec![article::author_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        Self::detach(prisma, user_id).await?;
+        AuditService::anonymize(prisma, user_id).await?;
+
+        prisma
+            .password_reset_token()
+            .delete_many(vec![password_reset_token::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .email_verification_token()
+            .delete_many(vec![email_verification_token::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .oidc_identity()
+            .delete_many(vec![oidc_identity::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .username_history()
+            .delete_many(vec![username_history::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user_settings()
+            .delete_many(vec![user_settings::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .user()
+            .delete(user::id::equals(user_id))
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use axum::{
+    extract::{Multipart, State},
+    Extension, Json,
+};
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageDecoder, ImageFormat,
+    ImageReader, Limits,
+};
+use std::{io::Cursor, sync::Arc};
+use tracing::error;
+
+use crate::{
+    app_error::{AppError, ValidationErrors},
+    config::AppContext,
+    domain::access_tokens::scope::Scope,
+    extractor::AuthUser,
+    prisma::{user, PrismaClient},
+};
+
+use super::{response::User, token::TokenService, UserBody};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+/// Largest accepted upload, enforced as the body limit of the avatar route.
+pub const MAX_AVATAR_BYTES: usize = 5 * 1024 * 1024;
+
+/// Square renditions generated for every avatar, in pixels.
+const SIZES: [u32; 4] = [64, 128, 256, 512];
+/// Rendition `Profile.image` points at. The others differ only in the file name.
+const DEFAULT_SIZE: u32 = 256;
+/// Guards against decompression bombs hiding behind a small upload.
+const MAX_DIMENSION: u32 = 8192;
+const JPEG_QUALITY: u8 = 85;
+const KEY_LENGTH: usize = 16;
+
+struct Rendition {
+    size: u32,
+    contents: Vec<u8>,
+}
+
+struct Renditions {
+    extension: &'static str,
+    content_type: &'static str,
+    images: Vec<Rendition>,
+}
+
+pub struct AvatarService;
+
+impl AvatarService {
+    /// Replaces the avatar with an uploaded image, sent as the `avatar` field of a
+    /// multipart form.
+    ///
+    /// The image is re-encoded, which drops EXIF and any other metadata, and stored
+    /// in every size of `SIZES`.
+    pub async fn upload_avatar(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        mut multipart: Multipart,
+    ) -> Result<Json<UserBody<User>>, AppError> {
+        auth_user.require_scope(Scope::ProfileWrite)?;
+
+        let mut upload = None;
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| AppError::BadRequest(e.body_text()))?
+        {
+            if field.name() == Some("avatar") {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| AppError::BadRequest(e.body_text()))?;
+                upload = Some(bytes.to_vec());
+                break;
+            }
+        }
+
+        let Some(upload) = upload else {
+            return Err(Self::invalid("is missing"));
+        };
+
+        let renditions = tokio::task::spawn_blocking(move || Self::process(&upload))
+            .await
+            .map_err(|e| anyhow::anyhow!("avatar processing task failed: {}", e))??;
+
+        let data = prisma
+            .user()
+            .find_first(vec![
+                user::id::equals(auth_user.user_id),
+                user::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        // A fresh prefix per upload, so caches never serve the previous avatar
+        let prefix = format!(
+            "avatars/{}/{}",
+            data.id,
+            TokenService::random_string(KEY_LENGTH).to_lowercase()
+        );
+        for rendition in renditions.images {
+            let key = format!("{}/{}.{}", prefix, rendition.size, renditions.extension);
+            ctx.storage
+                .put(&key, rendition.contents, renditions.content_type)
+                .await?;
+        }
+
+        let avatar_key = format!("{}/{}.{}", prefix, DEFAULT_SIZE, renditions.extension);
+        let updated = prisma
+            .user()
+            .update(
+                user::id::equals(data.id),
+                vec![
+                    user::image::set(Some(ctx.storage.url(&avatar_key))),
+                    user::avatar_key::set(Some(avatar_key)),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Self::remove_files(&ctx, data.avatar_key.as_deref()).await;
+
+        let mut user: User = updated.into();
+        if let Some(token) = auth_user.to_jwt(&ctx) {
+            user.set_token(token);
+        }
+
+        Ok(Json::from(UserBody { user }))
+    }
+
+    /// Deletes every rendition of a previous avatar. Failures only leave orphaned files.
+    pub(crate) async fn remove_files(ctx: &AppContext, avatar_key: Option<&str>) {
+        let Some((prefix, file)) = avatar_key.and_then(|key| key.rsplit_once('/')) else {
+            return;
+        };
+        let extension = file.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+        for size in SIZES {
+            let key = format!("{}/{}.{}", prefix, size, extension);
+
+            if let Err(e) = ctx.storage.delete(&key).await {
+                error!("failed to delete avatar file {}: {:?}", key, e);
+            }
+        }
+    }
+
+    fn process(upload: &[u8]) -> Result<Renditions, AppError> {
+        let format = image::guess_format(upload)
+            .ok()
+            .filter(|format| {
+                matches!(
+                    format,
+                    ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP | ImageFormat::Gif
+                )
+            })
+            .ok_or_else(|| Self::invalid("must be a JPEG, PNG, WebP or GIF image"))?;
+
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_DIMENSION);
+        limits.max_image_height = Some(MAX_DIMENSION);
+
+        let mut reader = ImageReader::with_format(Cursor::new(upload), format);
+        reader.limits(limits);
+
+        let decode = || -> image::ImageResult<DynamicImage> {
+            let mut decoder = reader.into_decoder()?;
+            // EXIF is dropped on re-encoding, so its rotation has to be applied first
+            let orientation = decoder.orientation()?;
+            let mut image = DynamicImage::from_decoder(decoder)?;
+            image.apply_orientation(orientation);
+            Ok(image)
+        };
+        let image = decode().map_err(|_| Self::invalid("could not be read as an image"))?;
+
+        // Transparent images stay PNG, everything else becomes a much smaller JPEG
+        let transparent = image.color().has_alpha();
+        let mut images = Vec::with_capacity(SIZES.len());
+
+        for size in SIZES {
+            // Scales the shorter side to `size` and crops the longer one around the center
+            let resized = image.resize_to_fill(size, size, FilterType::Lanczos3);
+            let mut contents = Vec::new();
+
+            if transparent {
+                resized
+                    .write_to(&mut Cursor::new(&mut contents), ImageFormat::Png)
+                    .map_err(|e| anyhow::anyhow!("failed to encode avatar: {}", e))?;
+            } else {
+                JpegEncoder::new_with_quality(&mut contents, JPEG_QUALITY)
+                    .encode_image(&resized.to_rgb8())
+                    .map_err(|e| anyhow::anyhow!("failed to encode avatar: {}", e))?;
+            }
+
+            images.push(Rendition { size, contents });
+        }
+
+        Ok(if transparent {
+            Renditions {
+                extension: "png",
+                content_type: "image/png",
+                images,
+            }
+        } else {
+            Renditions {
+                extension: "jpg",
+                content_type: "image/jpeg",
+                images,
+            }
+        })
+    }
+
+    fn invalid(message: &str) -> AppError {
+        let mut errors = ValidationErrors::default();
+        errors.add("avatar", message);
+        AppError::Validation(errors)
+    }
+}
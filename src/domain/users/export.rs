@@ -0,0 +1,402 @@
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use prisma_client_rust::{chrono, Direction};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, DuplexStream};
+use tokio_tar::{Builder, Header};
+use tokio_util::io::ReaderStream;
+use tracing::error;
+
+use crate::{
+    app_error::AppError,
+    domain::articles::response::{Article, Comment},
+    extractor::AuthUser,
+    prisma::{article, comment, session, user, user_favorite_article, user_follows, PrismaClient},
+};
+
+use super::response::{Session, User};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+/// Rows fetched per query, so large accounts never sit in memory all at once.
+const PAGE_SIZE: i64 = 100;
+const PIPE_CAPACITY: usize = 64 * 1024;
+/// Added as the last entry when the export fails part way.
+const ERROR_PATH: &str = "ERROR.txt";
+const ERROR_MESSAGE: &str =
+    "The export failed before it was complete, so files are missing from this archive. Please try again.\n";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedComment {
+    article_slug: String,
+    #[serde(flatten)]
+    comment: Comment,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedFavorite {
+    slug: String,
+    title: String,
+}
+
+pub struct ExportService;
+
+impl ExportService {
+    /// Streams a tar archive with everything stored about the current user.
+    ///
+    /// The archive is written by a background task while the response is being sent. If that
+    /// fails part way, the archive ends with an `ERROR.txt` entry.
+    pub async fn export(auth_user: AuthUser, prisma: Prisma) -> Result<Response, AppError> {
+        auth_user.require_session()?;
+
+        let data = prisma
+            .user()
+            .find_first(vec![
+                user::id::equals(auth_user.user_id),
+                user::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        let filename = format!(
+            "conduit-export-{}-{}.tar",
+            data.username,
+            chrono::Utc::now().format("%Y%m%d")
+        );
+
+        let (writer, reader) = tokio::io::duplex(PIPE_CAPACITY);
+        let prisma = Arc::clone(&prisma);
+        let session_id = auth_user.session_id.clone();
+
+        tokio::spawn(async move {
+            let user_id = data.id;
+
+            if let Err(e) = Self::write_archive(&prisma, data, session_id, writer).await {
+                error!("failed to export data of user {}: {:?}", user_id, e);
+            }
+        });
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, "application/x-tar".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+            ],
+            Body::from_stream(ReaderStream::new(reader)),
+        )
+            .into_response())
+    }
+
+    async fn write_archive(
+        prisma: &PrismaClient,
+        data: user::Data,
+        session_id: Option<String>,
+        writer: DuplexStream,
+    ) -> anyhow::Result<()> {
+        let mut archive = Builder::new(writer);
+
+        // The response has already started, so a failure can only be reported inside the archive
+        if let Err(e) = Self::write_entries(prisma, data, session_id, &mut archive).await {
+            // These fail too once the client is gone, the first error is the one worth logging
+            let _ = Self::append(&mut archive, ERROR_PATH, ERROR_MESSAGE.as_bytes()).await;
+            let _ = archive.into_inner().await;
+            return Err(e);
+        }
+
+        archive.into_inner().await?;
+
+        Ok(())
+    }
+
+    async fn write_entries<W: AsyncWrite + Unpin + Send + Sync>(
+        prisma: &PrismaClient,
+        data: user::Data,
+        session_id: Option<String>,
+        archive: &mut Builder<W>,
+    ) -> anyhow::Result<()> {
+        let user_id = data.id;
+
+        let profile: User = data.into();
+        Self::append_json(archive, "profile.json", &profile).await?;
+
+        Self::write_articles(prisma, user_id, archive).await?;
+        Self::write_comments(prisma, user_id, archive).await?;
+        Self::write_favorites(prisma, user_id, archive).await?;
+        Self::write_following(prisma, user_id, archive).await?;
+        Self::write_followers(prisma, user_id, archive).await?;
+
+        let sessions: Vec<Session> = prisma
+            .session()
+            .find_many(vec![session::user_id::equals(user_id)])
+            .order_by(session::created_at::order(Direction::Asc))
+            .exec()
+            .await?
+            .into_iter()
+            .map(|session| {
+                let current = session_id.as_deref() == Some(session.id.as_str());
+                session.to_session(current)
+            })
+            .collect();
+        Self::append_json(archive, "sessions.json", &sessions).await?;
+
+        Ok(())
+    }
+
+    /// Writes one Markdown file per article, with the metadata as YAML front-matter.
+    async fn write_articles<W: AsyncWrite + Unpin + Send + Sync>(
+        prisma: &PrismaClient,
+        user_id: i32,
+        archive: &mut Builder<W>,
+    ) -> anyhow::Result<()> {
+        let mut cursor = 0;
+
+        loop {
+            let page = prisma
+                .article()
+                .find_many(vec![
+                    article::author_id::equals(user_id),
+                    article::deleted_at::equals(None),
+                    article::id::gt(cursor),
+                ])
+                .with(article::author::fetch())
+                .with(article::tags::fetch(vec![]))
+                .order_by(article::id::order(Direction::Asc))
+                .take(PAGE_SIZE)
+                .exec()
+                .await?;
+
+            let Some(last) = page.last() else {
+                return Ok(());
+            };
+            cursor = last.id;
+
+            for data in page {
+                let article = data.to_article(false, false);
+                let path = format!("articles/{}.md", article.slug.replace('/', "-"));
+
+                Self::append(archive, &path, Self::to_markdown(&article)?.as_bytes()).await?;
+            }
+        }
+    }
+
+    /// Comments are split into numbered files of at most one page each.
+    async fn write_comments<W: AsyncWrite + Unpin + Send + Sync>(
+        prisma: &PrismaClient,
+        user_id: i32,
+        archive: &mut Builder<W>,
+    ) -> anyhow::Result<()> {
+        let mut cursor = 0;
+        let mut file_number = 1;
+
+        loop {
+            let page = prisma
+                .comment()
+                .find_many(vec![
+                    comment::author_id::equals(user_id),
+                    comment::deleted_at::equals(None),
+                    comment::id::gt(cursor),
+                ])
+                .with(comment::author::fetch())
+                .with(comment::article::fetch())
+                .order_by(comment::id::order(Direction::Asc))
+                .take(PAGE_SIZE)
+                .exec()
+                .await?;
+
+            let Some(last) = page.last() else {
+                return Ok(());
+            };
+            cursor = last.id;
+
+            let comments: Vec<ExportedComment> = page
+                .into_iter()
+                .map(|mut data| ExportedComment {
+                    article_slug: data
+                        .article
+                        .take()
+                        .map(|article| article.slug)
+                        .unwrap_or_default(),
+                    comment: data.to_comment(false),
+                })
+                .collect();
+
+            let path = format!("comments/{:04}.json", file_number);
+            Self::append_json(archive, &path, &comments).await?;
+            file_number += 1;
+        }
+    }
+
+    async fn write_favorites<W: AsyncWrite + Unpin + Send + Sync>(
+        prisma: &PrismaClient,
+        user_id: i32,
+        archive: &mut Builder<W>,
+    ) -> anyhow::Result<()> {
+        let mut cursor = 0;
+        let mut file_number = 1;
+
+        loop {
+            let page = prisma
+                .user_favorite_article()
+                .find_many(vec![
+                    user_favorite_article::user_id::equals(user_id),
+                    user_favorite_article::article_id::gt(cursor),
+                ])
+                .with(user_favorite_article::article::fetch())
+                .order_by(user_favorite_article::article_id::order(Direction::Asc))
+                .take(PAGE_SIZE)
+                .exec()
+                .await?;
+
+            let Some(last) = page.last() else {
+                return Ok(());
+            };
+            cursor = last.article_id;
+
+            let favorites: Vec<ExportedFavorite> = page
+                .into_iter()
+                .filter_map(|favorite| favorite.article)
+                .map(|article| ExportedFavorite {
+                    slug: article.slug,
+                    title: article.title,
+                })
+                .collect();
+
+            let path = format!("favorites/{:04}.json", file_number);
+            Self::append_json(archive, &path, &favorites).await?;
+            file_number += 1;
+        }
+    }
+
+    /// Usernames of the people the user follows.
+    async fn write_following<W: AsyncWrite + Unpin + Send + Sync>(
+        prisma: &PrismaClient,
+        user_id: i32,
+        archive: &mut Builder<W>,
+    ) -> anyhow::Result<()> {
+        let mut cursor = 0;
+        let mut file_number = 1;
+
+        loop {
+            let page = prisma
+                .user_follows()
+                .find_many(vec![
+                    user_follows::followed_by_id::equals(user_id),
+                    user_follows::following_id::gt(cursor),
+                ])
+                .with(user_follows::following::fetch())
+                .order_by(user_follows::following_id::order(Direction::Asc))
+                .take(PAGE_SIZE)
+                .exec()
+                .await?;
+
+            let Some(last) = page.last() else {
+                return Ok(());
+            };
+            cursor = last.following_id;
+
+            let following: Vec<String> = page
+                .into_iter()
+                .filter_map(|follow| follow.following.map(|user| user.username))
+                .collect();
+
+            let path = format!("following/{:04}.json", file_number);
+            Self::append_json(archive, &path, &following).await?;
+            file_number += 1;
+        }
+    }
+
+    /// Usernames of the people following the user.
+    async fn write_followers<W: AsyncWrite + Unpin + Send + Sync>(
+        prisma: &PrismaClient,
+        user_id: i32,
+        archive: &mut Builder<W>,
+    ) -> anyhow::Result<()> {
+        let mut cursor = 0;
+        let mut file_number = 1;
+
+        loop {
+            let page = prisma
+                .user_follows()
+                .find_many(vec![
+                    user_follows::following_id::equals(user_id),
+                    user_follows::followed_by_id::gt(cursor),
+                ])
+                .with(user_follows::followed_by::fetch())
+                .order_by(user_follows::followed_by_id::order(Direction::Asc))
+                .take(PAGE_SIZE)
+                .exec()
+                .await?;
+
+            let Some(last) = page.last() else {
+                return Ok(());
+            };
+            cursor = last.followed_by_id;
+
+            let followers: Vec<String> = page
+                .into_iter()
+                .filter_map(|follow| follow.followed_by.map(|user| user.username))
+                .collect();
+
+            let path = format!("followers/{:04}.json", file_number);
+            Self::append_json(archive, &path, &followers).await?;
+            file_number += 1;
+        }
+    }
+
+    fn to_markdown(article: &Article) -> anyhow::Result<String> {
+        // JSON strings are valid YAML scalars, which takes care of quoting
+        Ok(format!(
+            "---\n\
+             title: {}\n\
+             slug: {}\n\
+             description: {}\n\
+             tags: {}\n\
+             favoritesCount: {}\n\
+             createdAt: {}\n\
+             updatedAt: {}\n\
+             ---\n\n{}\n",
+            serde_json::to_string(&article.title)?,
+            serde_json::to_string(&article.slug)?,
+            serde_json::to_string(&article.description)?,
+            serde_json::to_string(&article.tag_list)?,
+            article.favorites_count,
+            article.created_at.to_rfc3339(),
+            article.updated_at.to_rfc3339(),
+            article.body,
+        ))
+    }
+
+    async fn append_json<W: AsyncWrite + Unpin + Send + Sync, T: Serialize>(
+        archive: &mut Builder<W>,
+        path: &str,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        Self::append(archive, path, &serde_json::to_vec_pretty(value)?).await
+    }
+
+    async fn append<W: AsyncWrite + Unpin + Send + Sync>(
+        archive: &mut Builder<W>,
+        path: &str,
+        contents: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp() as u64);
+        header.set_cksum();
+
+        archive.append_data(&mut header, path, contents).await?;
+
+        Ok(())
+    }
+}
@@ -1,10 +1,43 @@
 use serde::{Deserialize, Serialize};
 
+pub mod account;
+pub mod avatar;
+pub mod export;
+pub mod oidc;
+pub mod password;
+pub mod permission;
 pub mod request;
 pub mod response;
 pub mod service;
+pub mod session;
+pub mod settings;
+pub mod throttle;
+pub mod token;
+pub mod two_factor;
+pub mod username;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserBody<T> {
     pub user: T,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorBody<T> {
+    pub two_factor: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsBody<T> {
+    pub settings: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsBody<T> {
+    pub sessions: Vec<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcBody<T> {
+    pub oidc: T,
+}
@@ -0,0 +1,478 @@
+use axum::{extract::State, Extension, Json};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    DecodingKey, Validation,
+};
+use prisma_client_rust::chrono;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::{
+    app_error::AppError,
+    config::{oidc::OidcConfig, AppContext},
+    extractor::ClientInfo,
+    prisma::{
+        email_verification_token, oidc_identity, oidc_login_request, password_reset_token,
+        recovery_code, user, PrismaClient,
+    },
+};
+
+use super::{
+    request::OidcCallbackInput,
+    response::{LoginResponse, OidcAuthorization},
+    service::UsersService,
+    token::TokenService,
+    username::Username,
+    OidcBody, UserBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+const STATE_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 32;
+const CODE_VERIFIER_LENGTH: usize = 64;
+const LOGIN_REQUEST_EXP_IN_SEC: i64 = 600;
+/// Leaves room for a random suffix within the username length limit.
+const SUGGESTED_USERNAME_LENGTH: usize = 27;
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    preferred_username: Option<String>,
+}
+
+pub struct OidcService;
+
+impl OidcService {
+    /// Starts an authorization-code login and returns the provider URL to send the browser to.
+    pub async fn authorize(
+        prisma: Prisma,
+        ctx: State<AppContext>,
+    ) -> Result<Json<OidcBody<OidcAuthorization>>, AppError> {
+        let config = Self::config(&ctx)?;
+        let metadata = Self::discover(config).await?;
+
+        let state = TokenService::random_string(STATE_LENGTH);
+        let nonce = TokenService::random_string(NONCE_LENGTH);
+        let code_verifier = TokenService::random_string(CODE_VERIFIER_LENGTH);
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+        let now = chrono::Utc::now();
+        let expires_at = now + chrono::Duration::seconds(LOGIN_REQUEST_EXP_IN_SEC);
+
+        // Abandoned logins are cleaned up whenever a new one starts
+        prisma
+            .oidc_login_request()
+            .delete_many(vec![oidc_login_request::expires_at::lt(now.into())])
+            .exec()
+            .await?;
+
+        prisma
+            .oidc_login_request()
+            .create(
+                state.clone(),
+                code_verifier,
+                nonce.clone(),
+                expires_at.into(),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        let mut authorization_url = reqwest::Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| anyhow::anyhow!("invalid authorization endpoint: {}", e))?;
+        authorization_url
+            .query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &config.client_id)
+            .append_pair("redirect_uri", &config.redirect_uri)
+            .append_pair("scope", &config.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(Json::from(OidcBody {
+            oidc: OidcAuthorization {
+                authorization_url: authorization_url.to_string(),
+            },
+        }))
+    }
+
+    /// Finishes the login with the code the provider handed back to the frontend.
+    ///
+    /// The user is looked up by provider identity first, then linked by verified email,
+    /// and created without a password when neither matches. Linking an account whose email
+    /// was never verified drops its password, two-factor setup, tokens and sessions, since
+    /// whoever registered it did not prove they own the address.
+    pub async fn callback(
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        client: ClientInfo,
+        Json(input): Json<OidcBody<OidcCallbackInput>>,
+    ) -> Result<Json<UserBody<LoginResponse>>, AppError> {
+        let OidcBody {
+            oidc: OidcCallbackInput { code, state },
+        } = input;
+
+        let config = Self::config(&ctx)?;
+
+        let request = prisma
+            .oidc_login_request()
+            .find_unique(oidc_login_request::state::equals(state.clone()))
+            .exec()
+            .await?
+            .ok_or(AppError::Unauthorized(String::from(
+                "Invalid or expired login state",
+            )))?;
+
+        // Every state can only be redeemed once
+        prisma
+            .oidc_login_request()
+            .delete_many(vec![oidc_login_request::id::equals(request.id)])
+            .exec()
+            .await?;
+
+        if request.expires_at < chrono::Utc::now() {
+            return Err(AppError::Unauthorized(String::from(
+                "Invalid or expired login state",
+            )));
+        }
+
+        let metadata = Self::discover(config).await?;
+        let id_token =
+            Self::exchange_code(config, &metadata, &code, &request.code_verifier).await?;
+        let claims = Self::verify_id_token(config, &metadata, &id_token).await?;
+
+        if claims.nonce.as_deref() != Some(request.nonce.as_str()) {
+            debug!("ID token nonce does not match the login request");
+            return Err(AppError::Unauthorized(String::from(
+                "ID token validation failed",
+            )));
+        }
+
+        let data = Self::find_or_create_user(&prisma, &ctx, config, claims).await?;
+
+        UsersService::complete_login(&prisma, &ctx, data, &client).await
+    }
+
+    async fn find_or_create_user(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        config: &OidcConfig,
+        claims: IdTokenClaims,
+    ) -> Result<user::Data, AppError> {
+        let identity = prisma
+            .oidc_identity()
+            .find_unique(oidc_identity::issuer_subject(
+                config.issuer.clone(),
+                claims.sub.clone(),
+            ))
+            .with(oidc_identity::user::fetch())
+            .exec()
+            .await?;
+
+        if let Some(identity) = identity {
+            return Self::active(*identity.user.unwrap());
+        }
+
+        let email = match claims.email {
+            Some(email) if claims.email_verified => email.trim().to_lowercase(),
+            _ => {
+                return Err(AppError::Forbidden(String::from(
+                    "The identity provider did not return a verified email",
+                )))
+            }
+        };
+
+        let existing = prisma
+            .user()
+            .find_unique(user::email::equals(email.clone()))
+            .exec()
+            .await?;
+
+        let data = match existing {
+            // The provider vouches for the address, so the account counts as verified too
+            Some(data) => {
+                let data = Self::active(data)?;
+
+                if !data.email_verified {
+                    info!("clearing credentials of unverified user {}", data.id);
+                    Self::clear_credentials(prisma, data.id).await?;
+                }
+
+                info!("linking OIDC identity {} to user {}", claims.sub, data.id);
+
+                prisma
+                    .user()
+                    .update(
+                        user::id::equals(data.id),
+                        vec![user::email_verified::set(true)],
+                    )
+                    .exec()
+                    .await?
+            }
+            None => {
+                let username = Self::available_username(
+                    prisma,
+                    ctx,
+                    claims.preferred_username.as_deref().unwrap_or(&email),
+                )
+                .await?;
+
+                info!(
+                    "creating user {} from OIDC identity {}",
+                    username, claims.sub
+                );
+
+                let canonical_username = Username::canonical(&username);
+
+                prisma
+                    .user()
+                    .create(
+                        email.clone(),
+                        username,
+                        canonical_username,
+                        vec![user::email_verified::set(true)],
+                    )
+                    .exec()
+                    .await?
+            }
+        };
+
+        prisma
+            .oidc_identity()
+            .create(
+                config.issuer.clone(),
+                claims.sub,
+                email,
+                user::id::equals(data.id),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Signs the user out everywhere and removes every way of logging in without the
+    /// provider.
+    async fn clear_credentials(prisma: &PrismaClient, user_id: i32) -> Result<(), AppError> {
+        prisma
+            .user()
+            .update(
+                user::id::equals(user_id),
+                vec![
+                    user::password::set(None),
+                    user::totp_secret::set(None),
+                    user::totp_enabled::set(false),
+                    user::pending_email::set(None),
+                ],
+            )
+            .exec()
+            .await?;
+
+        TokenService::revoke_all_sessions(prisma, user_id).await?;
+        TokenService::revoke_access_tokens(prisma, user_id).await?;
+
+        prisma
+            .recovery_code()
+            .delete_many(vec![recovery_code::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .password_reset_token()
+            .delete_many(vec![password_reset_token::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .email_verification_token()
+            .delete_many(vec![email_verification_token::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    fn active(data: user::Data) -> Result<user::Data, AppError> {
+        if data.deleted_at.is_some() {
+            return Err(AppError::Forbidden(String::from(
+                "This account has been deleted",
+            )));
+        }
+
+        Ok(data)
+    }
+
+    /// Derives a username from the provider's suggestion, adding a suffix when it is taken
+    /// or breaks the username rules.
+    async fn available_username(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        hint: &str,
+    ) -> Result<String, AppError> {
+        let base: String = hint
+            .split('@')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+            .skip_while(|c| !c.is_ascii_alphanumeric())
+            .take(SUGGESTED_USERNAME_LENGTH)
+            .collect();
+        let base = if base.is_empty() {
+            "user".to_string()
+        } else {
+            base
+        };
+
+        let mut candidate = base.clone();
+        loop {
+            if Username::validate(&candidate).is_ok()
+                && Username::is_available(prisma, ctx, &candidate, None).await?
+            {
+                return Ok(candidate);
+            }
+
+            candidate = format!("{}-{}", base, TokenService::random_string(4).to_lowercase());
+        }
+    }
+
+    async fn discover(config: &OidcConfig) -> Result<ProviderMetadata, AppError> {
+        let metadata: ProviderMetadata = reqwest::get(&config.discovery_url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| anyhow::anyhow!("OIDC discovery failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("invalid OIDC discovery document: {}", e))?;
+
+        if metadata.issuer.trim_end_matches('/') != config.issuer {
+            return Err(AppError::Anyhow(anyhow::anyhow!(
+                "OIDC discovery issuer {} does not match {}",
+                metadata.issuer,
+                config.issuer
+            )));
+        }
+
+        Ok(metadata)
+    }
+
+    async fn exchange_code(
+        config: &OidcConfig,
+        metadata: &ProviderMetadata,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<String, AppError> {
+        let mut request = reqwest::Client::new()
+            .post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", config.redirect_uri.as_str()),
+                ("client_id", config.client_id.as_str()),
+                ("code_verifier", code_verifier),
+            ]);
+
+        if let Some(client_secret) = &config.client_secret {
+            request = request.basic_auth(&config.client_id, Some(client_secret));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("OIDC token request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            debug!("OIDC token endpoint returned {}", response.status());
+            return Err(AppError::Unauthorized(String::from(
+                "Authorization code was rejected",
+            )));
+        }
+
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("invalid OIDC token response: {}", e))?;
+
+        Ok(tokens.id_token)
+    }
+
+    async fn verify_id_token(
+        config: &OidcConfig,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let invalid = || AppError::Unauthorized(String::from("ID token validation failed"));
+
+        let header = jsonwebtoken::decode_header(id_token).map_err(|e| {
+            debug!("ID token header is malformed: {:?}", e);
+            invalid()
+        })?;
+
+        let jwks: JwkSet = reqwest::get(&metadata.jwks_uri)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| anyhow::anyhow!("OIDC JWKS request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("invalid OIDC JWKS: {}", e))?;
+
+        let jwk: &Jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or_else(|| {
+            debug!("ID token is signed with an unknown key: {:?}", header.kid);
+            invalid()
+        })?;
+
+        let decoding_key = DecodingKey::from_jwk(jwk).map_err(|e| {
+            debug!("Unsupported provider key: {:?}", e);
+            invalid()
+        })?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&config.client_id]);
+
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+            .map_err(|e| {
+                debug!("ID token validation failed: {:?}", e);
+                invalid()
+            })?
+            .claims;
+
+        Ok(claims)
+    }
+
+    fn config(ctx: &AppContext) -> Result<&OidcConfig, AppError> {
+        ctx.config
+            .oidc
+            .as_ref()
+            .ok_or(AppError::NotFound(String::from(
+                "OIDC login is not configured",
+            )))
+    }
+}
@@ -0,0 +1,472 @@
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use rand::rngs::OsRng;
+use sha1::{Digest, Sha1};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+use tracing::{error, info};
+
+use crate::{
+    app_error::{AppError, ValidationErrors},
+    config::{
+        password::{PasswordHashingConfig, PasswordPolicyConfig},
+        AppContext,
+    },
+};
+
+/// Minimum length of a username or email part before it counts as personal info.
+const PERSONAL_INFO_MIN_LENGTH: usize = 3;
+
+/// Breached passwords, kept sorted as the first 8 bytes of their SHA-1 hash.
+///
+/// Lines may hold the plain password or its SHA-1 hex digest, optionally followed by
+/// `:count` as in the Have I Been Pwned downloads. Plain passwords are taken as they are,
+/// including any surrounding whitespace.
+#[derive(Debug, Default)]
+pub struct BreachedPasswords(Vec<u64>);
+
+impl BreachedPasswords {
+    pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        let file = File::open(path).map_err(|e| {
+            anyhow::anyhow!("failed to open breached password list {}: {}", path, e)
+        })?;
+        let breached = Self::read(BufReader::new(file))?;
+
+        info!(
+            "loaded {} breached passwords from {}",
+            breached.0.len(),
+            path
+        );
+
+        Ok(breached)
+    }
+
+    fn read(reader: impl BufRead) -> anyhow::Result<Self> {
+        // Read line by line, the full lists are far larger than the resulting prefixes
+        let mut hashes = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let digest = line.split(':').next().unwrap_or_default();
+            hashes.push(
+                Self::parse_hex_prefix(digest)
+                    .unwrap_or_else(|| Self::prefix(&Sha1::digest(line.as_bytes()))),
+            );
+        }
+
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes.shrink_to_fit();
+
+        Ok(Self(hashes))
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        !self.0.is_empty()
+            && self
+                .0
+                .binary_search(&Self::prefix(&Sha1::digest(password.as_bytes())))
+                .is_ok()
+    }
+
+    fn prefix(digest: &[u8]) -> u64 {
+        u64::from_be_bytes(digest[..8].try_into().unwrap())
+    }
+
+    fn parse_hex_prefix(value: &str) -> Option<u64> {
+        if value.len() != 40 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        u64::from_str_radix(&value[..16], 16).ok()
+    }
+}
+
+pub struct PasswordPolicy;
+
+impl PasswordPolicy {
+    /// Checks a new password against the configured policy, reporting every violation.
+    pub fn validate(
+        ctx: &AppContext,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<(), AppError> {
+        Self::check(
+            &ctx.config.password,
+            &ctx.breached_passwords,
+            password,
+            username,
+            email,
+        )
+        .into_result()
+    }
+
+    fn check(
+        policy: &PasswordPolicyConfig,
+        breached_passwords: &BreachedPasswords,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> ValidationErrors {
+        let mut errors = ValidationErrors::default();
+
+        if password.chars().count() < policy.min_length {
+            errors.add(
+                "password",
+                format!("is too short (minimum is {} characters)", policy.min_length),
+            );
+        }
+
+        if policy.require_lowercase && !password.chars().any(char::is_lowercase) {
+            errors.add("password", "must contain a lowercase letter");
+        }
+
+        if policy.require_uppercase && !password.chars().any(char::is_uppercase) {
+            errors.add("password", "must contain an uppercase letter");
+        }
+
+        if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            errors.add("password", "must contain a digit");
+        }
+
+        if policy.require_symbol && password.chars().all(char::is_alphanumeric) {
+            errors.add("password", "must contain a symbol");
+        }
+
+        if policy.reject_personal_info {
+            let lowercase = password.to_lowercase();
+            let local_part = email.split('@').next().unwrap_or_default();
+
+            if Self::contains_part(&lowercase, username) {
+                errors.add("password", "must not contain your username");
+            }
+
+            if Self::contains_part(&lowercase, local_part) {
+                errors.add("password", "must not contain your email");
+            }
+        }
+
+        if breached_passwords.contains(password) {
+            errors.add("password", "has appeared in a data breach");
+        }
+
+        errors
+    }
+
+    fn contains_part(password: &str, part: &str) -> bool {
+        part.chars().count() >= PERSONAL_INFO_MIN_LENGTH && password.contains(&part.to_lowercase())
+    }
+}
+
+/// Outcome of checking a password against a stored hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordMatch {
+    Mismatch,
+    Match,
+    /// The password is correct, but the hash is weaker than the current settings.
+    Outdated,
+}
+
+impl PasswordMatch {
+    pub fn is_match(self) -> bool {
+        self != Self::Mismatch
+    }
+}
+
+/// Argon2id hashing with the configured cost and pepper.
+///
+/// Hashing is deliberately slow, so it always runs on the blocking thread pool.
+pub struct PasswordHashing;
+
+impl PasswordHashing {
+    /// Fails on startup instead of on the first login when the parameters are invalid.
+    pub fn check_config(config: &PasswordHashingConfig) -> anyhow::Result<()> {
+        Self::argon2(config, true).map(|_| ())
+    }
+
+    pub async fn hash(ctx: &AppContext, password: &str) -> Result<String, AppError> {
+        let config = ctx.config.password_hashing.clone();
+        let password = password.to_string();
+
+        Self::blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+
+            // Hash password to PHC string ($argon2id$v=19$...)
+            let password_hash = Self::argon2(&config, true)?
+                .hash_password(password.as_bytes(), &salt)
+                .map_err(|_| anyhow::anyhow!("failed to hash password"))?;
+
+            Ok(password_hash.to_string())
+        })
+        .await
+    }
+
+    /// Checks a password against a stored hash.
+    ///
+    /// A hash that can't be checked, such as a malformed or legacy one, counts as a mismatch,
+    /// so callers answer like for a wrong password instead of failing with a server error.
+    pub async fn verify(ctx: &AppContext, password: &str, password_hash: &str) -> PasswordMatch {
+        let config = ctx.config.password_hashing.clone();
+        let password = password.to_string();
+        let password_hash = password_hash.to_string();
+
+        let result = Self::blocking(move || {
+            let Ok(parsed) = PasswordHash::new(&password_hash) else {
+                // Takes as long as a real check, so broken hashes don't stand out
+                Self::argon2(&config, true)?
+                    .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+                    .map_err(|_| anyhow::anyhow!("failed to hash password"))?;
+
+                anyhow::bail!("failed to parse password hash from PHC string");
+            };
+
+            // Verification uses the parameters stored in the hash, only the pepper comes from us
+            if Self::argon2(&config, true)?
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+            {
+                return Ok(if Self::is_outdated(&config, &parsed) {
+                    PasswordMatch::Outdated
+                } else {
+                    PasswordMatch::Match
+                });
+            }
+
+            // Hashes from before the pepper was configured are upgraded on the next login
+            if config.pepper.is_some()
+                && Self::argon2(&config, false)?
+                    .verify_password(password.as_bytes(), &parsed)
+                    .is_ok()
+            {
+                return Ok(PasswordMatch::Outdated);
+            }
+
+            Ok(PasswordMatch::Mismatch)
+        })
+        .await;
+
+        result.unwrap_or_else(|e| {
+            error!("failed to verify password: {:?}", e);
+            PasswordMatch::Mismatch
+        })
+    }
+
+    fn argon2(config: &PasswordHashingConfig, peppered: bool) -> anyhow::Result<Argon2<'_>> {
+        let params = Params::new(
+            config.memory_cost_kib,
+            config.time_cost,
+            config.parallelism,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("invalid Argon2 parameters: {}", e))?;
+
+        match config.pepper.as_deref() {
+            Some(pepper) if peppered => Argon2::new_with_secret(
+                pepper.as_bytes(),
+                Algorithm::Argon2id,
+                Version::V0x13,
+                params,
+            )
+            .map_err(|e| anyhow::anyhow!("invalid password pepper: {}", e)),
+            _ => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+
+    fn is_outdated(config: &PasswordHashingConfig, hash: &PasswordHash) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13 as u32)
+        {
+            return true;
+        }
+
+        Params::try_from(hash).map_or(true, |params| {
+            params.m_cost() < config.memory_cost_kib
+                || params.t_cost() < config.time_cost
+                || params.p_cost() < config.parallelism
+        })
+    }
+
+    async fn blocking<T: Send + 'static>(
+        f: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+    ) -> Result<T, AppError> {
+        let result = tokio::task::spawn_blocking(f)
+            .await
+            .map_err(|e| anyhow::anyhow!("password hashing task failed: {}", e))?;
+
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-1 of `password`.
+    const PASSWORD_SHA1: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+    fn breached(list: &str) -> BreachedPasswords {
+        BreachedPasswords::read(list.as_bytes()).unwrap()
+    }
+
+    fn policy() -> PasswordPolicyConfig {
+        PasswordPolicyConfig {
+            min_length: 8,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            reject_personal_info: false,
+            breached_passwords_file: None,
+        }
+    }
+
+    fn messages(errors: ValidationErrors) -> Vec<String> {
+        let mut errors = serde_json::to_value(errors).unwrap();
+        serde_json::from_value(errors["password"].take()).unwrap_or_default()
+    }
+
+    fn check(policy: &PasswordPolicyConfig, password: &str) -> Vec<String> {
+        messages(PasswordPolicy::check(
+            policy,
+            &BreachedPasswords::default(),
+            password,
+            "jake",
+            "jdoe@example.com",
+        ))
+    }
+
+    #[test]
+    fn breached_passwords_read_plain_lines() {
+        let breached = breached("password\n123456\r\n\n");
+
+        assert!(breached.contains("password"));
+        assert!(breached.contains("123456"));
+        assert!(!breached.contains("Password"));
+        assert!(!breached.contains(""));
+    }
+
+    #[test]
+    fn breached_passwords_read_hash_count_lines() {
+        assert!(breached(&format!("{}:3730471\n", PASSWORD_SHA1)).contains("password"));
+        assert!(breached(PASSWORD_SHA1).contains("password"));
+        assert!(breached(&PASSWORD_SHA1.to_lowercase()).contains("password"));
+    }
+
+    #[test]
+    fn breached_passwords_hash_lines_that_are_not_digests() {
+        // One digit short of a digest, so the line is a password of its own
+        let line = &PASSWORD_SHA1[..39];
+        let breached = breached(line);
+
+        assert!(!breached.contains("password"));
+        assert!(breached.contains(line));
+    }
+
+    #[test]
+    fn breached_passwords_keep_surrounding_whitespace() {
+        let breached = breached(" hunter2\t\n");
+
+        assert!(breached.contains(" hunter2\t"));
+        assert!(!breached.contains("hunter2"));
+    }
+
+    #[test]
+    fn breached_passwords_without_a_list_contain_nothing() {
+        assert!(!BreachedPasswords::default().contains("password"));
+        assert!(BreachedPasswords::load(None).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn policy_counts_characters_for_the_length() {
+        assert!(check(&policy(), "12345678").is_empty());
+        assert_eq!(
+            check(&policy(), "1234567"),
+            ["is too short (minimum is 8 characters)"]
+        );
+        // Eight characters, but more bytes
+        assert!(check(&policy(), "ääääääää").is_empty());
+    }
+
+    #[test]
+    fn policy_reports_every_missing_class() {
+        let policy = PasswordPolicyConfig {
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..policy()
+        };
+
+        assert_eq!(
+            check(&policy, "        "),
+            [
+                "must contain a lowercase letter",
+                "must contain an uppercase letter",
+                "must contain a digit",
+            ]
+        );
+        assert_eq!(
+            check(&policy, "abcdEFGH"),
+            ["must contain a digit", "must contain a symbol"]
+        );
+        assert!(check(&policy, "abcdEF1!").is_empty());
+    }
+
+    #[test]
+    fn policy_rejects_personal_info_regardless_of_case() {
+        let policy = PasswordPolicyConfig {
+            reject_personal_info: true,
+            ..policy()
+        };
+
+        assert_eq!(
+            check(&policy, "iamJAKE123"),
+            ["must not contain your username"]
+        );
+        assert_eq!(check(&policy, "xJDoe!!!"), ["must not contain your email"]);
+        assert!(check(&policy, "correct horse").is_empty());
+    }
+
+    #[test]
+    fn policy_ignores_short_personal_info() {
+        let policy = PasswordPolicyConfig {
+            reject_personal_info: true,
+            ..policy()
+        };
+
+        let errors = PasswordPolicy::check(
+            &policy,
+            &BreachedPasswords::default(),
+            "joe-is-not-me",
+            "jo",
+            "jo@example.com",
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn policy_rejects_breached_passwords() {
+        let errors = PasswordPolicy::check(
+            &policy(),
+            &breached(PASSWORD_SHA1),
+            "password",
+            "jake",
+            "jake@example.com",
+        );
+
+        assert_eq!(messages(errors), ["has appeared in a data breach"]);
+    }
+}
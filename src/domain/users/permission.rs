@@ -0,0 +1,25 @@
+use crate::prisma::Role;
+
+/// Actions that go beyond what a user may do with their own content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Edit or delete articles written by someone else.
+    ModerateArticles,
+    /// Delete comments written by someone else.
+    ModerateComments,
+    /// Read the audit log of every account. Only admins have it.
+    ViewAuditLog,
+}
+
+impl Permission {
+    pub fn is_granted_to(&self, role: Role) -> bool {
+        match role {
+            Role::Admin => true,
+            Role::Moderator => matches!(
+                self,
+                Permission::ModerateArticles | Permission::ModerateComments
+            ),
+            Role::User => false,
+        }
+    }
+}
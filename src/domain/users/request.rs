@@ -1,5 +1,7 @@
 use serde::Deserialize;
 
+use crate::prisma::FeedSort;
+
 #[derive(Debug, Deserialize)]
 pub struct UserCreateInput {
     pub username: String,
@@ -14,6 +16,22 @@ pub struct UserUpdateInput {
     pub bio: Option<String>,
     pub image: Option<String>,
     pub password: Option<String>,
+    /// Private accounts approve their followers. Going public approves all pending requests.
+    pub private: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountInput {
+    /// Left out by accounts without a password, which confirm with a fresh login instead.
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogoutAllQuery {
+    /// Also revokes personal access tokens, which otherwise keep working.
+    #[serde(default)]
+    pub access_tokens: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,3 +39,77 @@ pub struct UserLoginInput {
     pub email: String,
     pub password: String,
 }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenInput {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordInput {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordInput {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailInput {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorLoginInput {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeInput {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorDisableInput {
+    /// Left out by accounts without a password, which confirm with a fresh login instead.
+    pub password: Option<String>,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackInput {
+    pub code: String,
+    pub state: String,
+}
+
+/// Every field is optional, leaving out a setting keeps its current value.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsUpdateInput {
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub feed_sort: Option<FeedSort>,
+    pub email_notifications: Option<EmailNotificationsInput>,
+    pub content_filters: Option<ContentFiltersInput>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailNotificationsInput {
+    pub new_follower: Option<bool>,
+    pub follow_request: Option<bool>,
+    pub comment: Option<bool>,
+    pub favorite: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentFiltersInput {
+    pub hidden_tags: Option<Vec<String>>,
+    pub muted_words: Option<Vec<String>>,
+}
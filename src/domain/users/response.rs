@@ -1,28 +1,45 @@
 use serde::{Deserialize, Serialize};
 use prisma_client_rust::chrono::{FixedOffset, TimeZone};
 
-use crate::prisma::user;
+use crate::prisma::{session, user, user_settings, FeedSort, Role};
+
+use super::token::IssuedTokens;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
     pub id: i32,
     pub email: String,
+    pub email_verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_email: Option<String>,
+    pub two_factor_enabled: bool,
+    /// False for accounts created through OIDC until a password is set.
+    pub has_password: bool,
     pub username: String,
+    pub role: Role,
     pub bio: Option<String>,
     pub image: Option<String>,
+    pub private: bool,
     pub created_at:
         ::prisma_client_rust::chrono::DateTime<FixedOffset>,
     pub updated_at:
         ::prisma_client_rust::chrono::DateTime<FixedOffset>,
 
     pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
 }
 
 impl User {
     pub fn set_token(&mut self, token: String) {
         self.token = Some(token);
     }
+
+    pub fn set_tokens(&mut self, tokens: IssuedTokens) {
+        self.token = Some(tokens.access_token);
+        self.refresh_token = Some(tokens.refresh_token);
+    }
 }
 
 impl From<user::Data> for User {
@@ -30,12 +47,125 @@ impl From<user::Data> for User {
         Self {
             id: data.id,
             email: data.email,
+            email_verified: data.email_verified,
+            pending_email: data.pending_email,
+            two_factor_enabled: data.totp_enabled,
+            has_password: data.password.is_some(),
             username: data.username,
+            role: data.role,
             bio: data.bio,
             image: data.image,
+            private: data.private,
             created_at: data.created_at,
             updated_at: data.updated_at,
             token: None,
+            refresh_token: None,
+        }
+    }
+}
+
+/// Login either completes right away or asks for a second factor.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    User(User),
+    Challenge(TwoFactorChallenge),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcAuthorization {
+    pub authorization_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at:
+        ::prisma_client_rust::chrono::DateTime<FixedOffset>,
+    pub last_seen_at:
+        ::prisma_client_rust::chrono::DateTime<FixedOffset>,
+    pub current: bool,
+}
+
+impl session::Data {
+    pub fn to_session(self, current: bool) -> Session {
+        Session {
+            id: self.id,
+            user_agent: self.user_agent,
+            ip: self.ip,
+            created_at: self.created_at,
+            last_seen_at: self.last_seen_at,
+            current,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Settings {
+    pub locale: String,
+    pub timezone: String,
+    pub feed_sort: FeedSort,
+    pub email_notifications: EmailNotifications,
+    pub content_filters: ContentFilters,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailNotifications {
+    pub new_follower: bool,
+    pub follow_request: bool,
+    pub comment: bool,
+    pub favorite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentFilters {
+    pub hidden_tags: Vec<String>,
+    pub muted_words: Vec<String>,
+}
+
+impl From<user_settings::Data> for Settings {
+    fn from(data: user_settings::Data) -> Self {
+        Self {
+            locale: data.locale,
+            timezone: data.timezone,
+            feed_sort: data.feed_sort,
+            email_notifications: EmailNotifications {
+                new_follower: data.email_on_follower,
+                follow_request: data.email_on_follow_request,
+                comment: data.email_on_comment,
+                favorite: data.email_on_favorite,
+            },
+            content_filters: ContentFilters {
+                hidden_tags: serde_json::from_value(data.hidden_tags).unwrap_or_default(),
+                muted_words: serde_json::from_value(data.muted_words).unwrap_or_default(),
+            },
         }
     }
 }
@@ -1,19 +1,36 @@
-use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use axum::{extract::State, Extension, Json};
-use rand::rngs::OsRng;
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use prisma_client_rust::chrono;
 use std::sync::Arc;
 use regex::Regex;
+use tracing::error;
 
 use crate::{
-    app_error::AppError,
+    app_error::{AppError, ValidationErrors},
     config::AppContext,
-    extractor::AuthUser,
-    prisma::{user, PrismaClient},
+    domain::{
+        access_tokens::scope::Scope, audit::service::AuditService,
+        profiles::follow_request::FollowRequestsService,
+    },
+    extractor::{AuthUser, ClientInfo},
+    mailer::Email,
+    prisma::{email_verification_token, password_reset_token, user, AuditAction, PrismaClient},
 };
 
 use super::{
-    request::{UserCreateInput, UserLoginInput, UserUpdateInput},
-    response::User,
+    avatar::AvatarService,
+    password::{PasswordHashing, PasswordMatch, PasswordPolicy},
+    request::{
+        ForgotPasswordInput, LogoutAllQuery, RefreshTokenInput, ResetPasswordInput,
+        TwoFactorLoginInput, UserCreateInput, UserLoginInput, UserUpdateInput, VerifyEmailInput,
+    },
+    response::{LoginResponse, TwoFactorChallenge, User},
+    throttle::LoginThrottleService,
+    token::TokenService,
+    two_factor::TwoFactorService,
+    username::Username,
     UserBody,
 };
 
@@ -27,6 +44,8 @@ impl UsersService {
         ctx: State<AppContext>,
         prisma: Prisma,
     ) -> Result<Json<UserBody<User>>, AppError> {
+        auth_user.require_scope(Scope::ProfileRead)?;
+
         let data = prisma
             .user()
             .find_unique(user::id::equals(auth_user.user_id))
@@ -37,7 +56,9 @@ impl UsersService {
         match data {
             Some(data) => {
                 let mut user: User = data.into();
-                user.set_token(auth_user.to_jwt(&ctx));
+                if let Some(token) = auth_user.to_jwt(&ctx) {
+                    user.set_token(token);
+                }
 
                 Ok(Json::from(UserBody { user }))
             }
@@ -48,6 +69,7 @@ impl UsersService {
     pub async fn create_user(
         prisma: Prisma,
         ctx: State<AppContext>,
+        client: ClientInfo,
         Json(input): Json<UserBody<UserCreateInput>>,
     ) -> Result<Json<UserBody<User>>, AppError> {
         let UserBody {
@@ -60,22 +82,32 @@ impl UsersService {
         } = input;
 
         Self::is_valid_email(&email)?;
+        Username::validate(&username)?;
+        PasswordPolicy::validate(&ctx, &password, &username, &email)?;
+        Username::check_available(&prisma, &ctx, &username, None).await?;
+
+        let canonical_username = Username::canonical(&username);
 
         let data = prisma
             .user()
             .create(
                 email,
-                Self::hash_password(password.as_str()).unwrap(),
                 username,
-                vec![],
+                canonical_username,
+                vec![user::password::set(Some(
+                    PasswordHashing::hash(&ctx, &password).await?,
+                ))],
             )
             .exec()
             .await?;
 
-        let token = AuthUser { user_id: data.id }.to_jwt(&ctx);
+        Self::send_verification_email(&prisma, &ctx, &data.username, data.id, data.email.clone())
+            .await?;
+
+        let tokens = TokenService::issue(&prisma, &ctx, data.id, &client).await?;
 
         let mut user: User = data.into();
-        user.set_token(token);
+        user.set_tokens(tokens);
 
         Ok(Json::from(UserBody { user }))
     }
@@ -84,6 +116,7 @@ impl UsersService {
         prisma: Prisma,
         auth_user: AuthUser,
         ctx: State<AppContext>,
+        client: ClientInfo,
         Json(input): Json<UserBody<UserUpdateInput>>,
     ) -> Result<Json<UserBody<User>>, AppError> {
         let UserBody {
@@ -94,9 +127,17 @@ impl UsersService {
                     image,
                     username,
                     password,
+                    private,
                 },
         } = input;
 
+        auth_user.require_scope(Scope::ProfileWrite)?;
+
+        // Credentials can only be changed from a login session
+        if email.is_some() || password.is_some() {
+            auth_user.require_session()?;
+        }
+
         if let Some(email) = &email{
             Self::is_valid_email(email)?;
         }
@@ -108,6 +149,40 @@ impl UsersService {
             .await?
             .unwrap();
 
+        // Names from before the rules existed can be kept, but not picked anew
+        match &username {
+            Some(username) if *username != data.username => Username::validate(username)?,
+            _ => {}
+        }
+
+        // Only a new canonical name is a rename, changing the casing keeps the old links working
+        let previous_username = match &username {
+            Some(username) if Username::canonical(username) != data.canonical_username => {
+                Username::check_available(&prisma, &ctx, username, Some(data.id)).await?;
+                Some(data.username.clone())
+            }
+            _ => None,
+        };
+
+        if let Some(password) = &password {
+            PasswordPolicy::validate(
+                &ctx,
+                password,
+                username.as_deref().unwrap_or(&data.username),
+                email.as_deref().unwrap_or(&data.email),
+            )?;
+        }
+
+        let pending_email = email.filter(|email| *email != data.email);
+        if let Some(pending_email) = &pending_email {
+            Self::check_email_available(&prisma, pending_email).await?;
+        }
+        // Settings forms send the current image back on every save, which keeps the avatar
+        let image = image.filter(|image| data.image.as_ref() != Some(image));
+        let previous_avatar_key = data.avatar_key.clone();
+        let was_private = data.private;
+        let password_changed = password.is_some();
+
         let data = prisma
             .user()
             .update(
@@ -117,33 +192,78 @@ impl UsersService {
                         Some(bio) => user::bio::set(Some(bio)),
                         None => user::bio::set(data.bio),
                     },
+                    // An image URL from the client replaces the uploaded avatar
+                    match &image {
+                        Some(_) => user::avatar_key::set(None),
+                        None => user::avatar_key::set(data.avatar_key.clone()),
+                    },
                     match image {
                         Some(image) => user::image::set(Some(image)),
                         None => user::image::set(data.image),
                     },
-                    match email {
-                        Some(email) => user::email::set(email),
-                        None => user::email::set(data.email),
+                    // A new email stays pending until the address is confirmed
+                    match &pending_email {
+                        Some(pending_email) => {
+                            user::pending_email::set(Some(pending_email.clone()))
+                        }
+                        None => user::pending_email::set(data.pending_email),
+                    },
+                    match &username {
+                        Some(username) => {
+                            user::canonical_username::set(Username::canonical(username))
+                        }
+                        None => user::canonical_username::set(data.canonical_username),
                     },
                     match username {
                         Some(username) => user::username::set(username),
                         None => user::username::set(data.username),
                     },
+                    // Also lets accounts created through OIDC set their first password
                     match password {
                         Some(password) => {
-                            user::password::set(Self::hash_password(password.as_str()).unwrap())
+                            user::password::set(Some(PasswordHashing::hash(&ctx, &password).await?))
                         }
                         None => user::password::set(data.password),
                     },
+                    user::private::set(private.unwrap_or(data.private)),
                 ],
             )
             .exec()
             .await?;
 
-        let mut user: User = data.into();
+        if data.avatar_key.is_none() {
+            AvatarService::remove_files(&ctx, previous_avatar_key.as_deref()).await;
+        }
+
+        if password_changed {
+            AuditService::record(
+                &prisma,
+                &client,
+                AuditAction::PasswordChanged,
+                Some(data.id),
+                None,
+                None,
+            )
+            .await;
+        }
+
+        if was_private && !data.private {
+            FollowRequestsService::approve_all(&prisma, data.id).await?;
+        }
+
+        if let Some(previous_username) = previous_username {
+            Username::record_rename(&prisma, data.id, &previous_username).await?;
+        }
+
+        if let Some(pending_email) = pending_email {
+            Self::send_verification_email(&prisma, &ctx, &data.username, data.id, pending_email)
+                .await?;
+        }
 
-        let token = AuthUser { user_id: user.id }.to_jwt(&ctx);
-        user.set_token(token);
+        let mut user: User = data.into();
+        if let Some(token) = auth_user.to_jwt(&ctx) {
+            user.set_token(token);
+        }
 
         Ok(Json::from(UserBody { user }))
     }
@@ -151,63 +271,630 @@ impl UsersService {
     pub async fn login(
         prisma: Prisma,
         ctx: State<AppContext>,
+        client: ClientInfo,
         Json(input): Json<UserBody<UserLoginInput>>,
-    ) -> Result<Json<UserBody<User>>, AppError> {
+    ) -> Result<Json<UserBody<LoginResponse>>, AppError> {
         let UserBody {
             user: UserLoginInput { email, password },
         } = input;
 
+        let account_key = LoginThrottleService::account_key(&email);
+        let ip_key = LoginThrottleService::ip_key(&client.ip);
+
+        LoginThrottleService::check(&prisma, &[account_key.clone(), ip_key.clone()]).await?;
+
         let data = prisma
             .user()
-            .find_unique(user::email::equals(email))
+            .find_first(vec![
+                user::email::equals(email.clone()),
+                user::deleted_at::equals(None),
+            ])
             .exec()
             .await?;
 
+        let password_hash = data.as_ref().and_then(|data| data.password.as_deref());
+
+        let verified = match password_hash {
+            Some(password_hash) => PasswordHashing::verify(&ctx, &password, password_hash).await,
+            // Unknown emails and accounts without a password take as long as a wrong password
+            None => {
+                PasswordHashing::hash(&ctx, &password).await?;
+                PasswordMatch::Mismatch
+            }
+        };
+
+        // Unknown emails and wrong passwords get the same response
         let data = match data {
-            Some(user_data) => user_data,
-            None => return Err(AppError::NotFound(String::from("User not found"))),
+            Some(data) if verified.is_match() => data,
+            data => {
+                // The attempt is not the account owner's doing, and what was typed as the
+                // email is not kept since it may be a typo or even a pasted password
+                AuditService::record(
+                    &prisma,
+                    &client,
+                    AuditAction::LoginFailed,
+                    None,
+                    data.map(|data| data.id),
+                    None,
+                )
+                .await;
+
+                return Self::login_failed(&prisma, &ctx, &[account_key, ip_key]).await;
+            }
         };
 
-        Self::verify_password(password.as_str(), data.password.as_str())?;
+        LoginThrottleService::reset(&prisma, &account_key).await?;
+
+        if verified == PasswordMatch::Outdated {
+            Self::rehash_password(&prisma, &ctx, data.id, &password).await;
+        }
+
+        Self::complete_login(&prisma, &ctx, data, &client).await
+    }
+
+    /// Upgrades a hash made with weaker settings. Failures only cost the upgrade, not the login.
+    async fn rehash_password(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        user_id: i32,
+        password: &str,
+    ) {
+        let result: Result<(), AppError> = async {
+            let password_hash = PasswordHashing::hash(ctx, password).await?;
+
+            prisma
+                .user()
+                .update(
+                    user::id::equals(user_id),
+                    vec![user::password::set(Some(password_hash))],
+                )
+                .exec()
+                .await?;
+
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            error!("failed to rehash password of user {}: {:?}", user_id, e);
+        }
+    }
+
+    /// Issues tokens for an authenticated user, or a challenge when two-factor is enabled.
+    pub(crate) async fn complete_login(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        data: user::Data,
+        client: &ClientInfo,
+    ) -> Result<Json<UserBody<LoginResponse>>, AppError> {
+        if data.totp_enabled {
+            return Ok(Json::from(UserBody {
+                user: LoginResponse::Challenge(TwoFactorChallenge {
+                    two_factor_required: true,
+                    challenge_token: TokenService::issue_challenge(prisma, ctx, data.id).await?,
+                }),
+            }));
+        }
+
+        let tokens = TokenService::issue(prisma, ctx, data.id, client).await?;
+
+        AuditService::record(
+            prisma,
+            client,
+            AuditAction::LoginSucceeded,
+            Some(data.id),
+            None,
+            None,
+        )
+        .await;
+
+        let mut user: User = data.into();
+        user.set_tokens(tokens);
+
+        Ok(Json::from(UserBody {
+            user: LoginResponse::User(user),
+        }))
+    }
+
+    pub async fn login_two_factor(
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        client: ClientInfo,
+        Json(input): Json<UserBody<TwoFactorLoginInput>>,
+    ) -> Result<Json<UserBody<User>>, AppError> {
+        let UserBody {
+            user:
+                TwoFactorLoginInput {
+                    challenge_token,
+                    code,
+                },
+        } = input;
+
+        let challenge =
+            TokenService::verify_challenge(&prisma, &ctx, challenge_token.as_str()).await?;
+
+        let data = prisma
+            .user()
+            .find_unique(user::id::equals(challenge.user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        let account_key = LoginThrottleService::account_key(&data.email);
+        let ip_key = LoginThrottleService::ip_key(&client.ip);
+
+        LoginThrottleService::check(&prisma, &[account_key.clone(), ip_key.clone()]).await?;
+
+        if !TwoFactorService::verify_code(&prisma, &ctx, &data, code.as_str()).await? {
+            AuditService::record(
+                &prisma,
+                &client,
+                AuditAction::LoginFailed,
+                None,
+                Some(data.id),
+                Some("two-factor code"),
+            )
+            .await;
+
+            for key in [account_key, ip_key] {
+                LoginThrottleService::record_failure(&prisma, &ctx, &key).await?;
+            }
+
+            return Err(AppError::Unauthorized(String::from(
+                "Invalid two-factor code",
+            )));
+        }
+
+        // Only one login can come out of a challenge, however often the token is replayed
+        TokenService::consume_challenge(&prisma, &challenge.jti).await?;
+
+        LoginThrottleService::reset(&prisma, &account_key).await?;
+
+        let tokens = TokenService::issue(&prisma, &ctx, data.id, &client).await?;
+
+        AuditService::record(
+            &prisma,
+            &client,
+            AuditAction::LoginSucceeded,
+            Some(data.id),
+            None,
+            None,
+        )
+        .await;
+
         let mut user: User = data.into();
+        user.set_tokens(tokens);
+
+        Ok(Json::from(UserBody { user }))
+    }
+
+    pub async fn refresh(
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        Json(input): Json<UserBody<RefreshTokenInput>>,
+    ) -> Result<Json<UserBody<User>>, AppError> {
+        let UserBody {
+            user: RefreshTokenInput { refresh_token },
+        } = input;
 
-        let token = AuthUser { user_id: user.id }.to_jwt(&ctx);
-        user.set_token(token);
+        let tokens = TokenService::rotate(&prisma, &ctx, refresh_token.as_str()).await?;
+
+        let data = prisma
+            .user()
+            .find_unique(user::id::equals(tokens.user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        let mut user: User = data.into();
+        user.set_tokens(tokens);
 
         Ok(Json::from(UserBody { user }))
     }
 
-    fn is_valid_email(email: &str) -> Result<(), AppError> {
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(email) {
-            return Err(AppError::BadRequest(String::from("Invalid email format")));
+    pub async fn logout(auth_user: AuthUser, prisma: Prisma) -> Result<Json<String>, AppError> {
+        let session_id = auth_user.require_session()?;
+
+        TokenService::revoke_session(&prisma, session_id).await?;
+
+        Ok(Json::from("Logged out".to_string()))
+    }
+
+    pub async fn logout_all(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        Query(query): Query<LogoutAllQuery>,
+    ) -> Result<Json<String>, AppError> {
+        auth_user.require_session()?;
+
+        TokenService::revoke_all_sessions(&prisma, auth_user.user_id).await?;
+
+        if query.access_tokens {
+            TokenService::revoke_access_tokens(&prisma, auth_user.user_id).await?;
         }
+
+        Ok(Json::from("Logged out from all sessions".to_string()))
+    }
+
+    pub async fn forgot_password(
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        Json(input): Json<UserBody<ForgotPasswordInput>>,
+    ) -> Result<Json<String>, AppError> {
+        let UserBody {
+            user: ForgotPasswordInput { email },
+        } = input;
+
+        let response = Json::from(
+            "If the email is registered, a password reset link has been sent".to_string(),
+        );
+
+        // Respond the same way for unknown emails so accounts can't be enumerated
+        let data = match prisma
+            .user()
+            .find_first(vec![
+                user::email::equals(email),
+                user::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?
+        {
+            Some(data) => data,
+            None => return Ok(response),
+        };
+
+        // The token and the email are written off the request path, so registered emails
+        // don't take longer to answer than unknown ones
+        let prisma = Arc::clone(&prisma);
+        tokio::spawn(async move {
+            let user_id = data.id;
+
+            if let Err(e) = Self::send_password_reset_email(&prisma, &ctx, data).await {
+                error!(
+                    "failed to send password reset email to user {}: {:?}",
+                    user_id, e
+                );
+            }
+        });
+
+        Ok(response)
+    }
+
+    pub async fn reset_password(
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        client: ClientInfo,
+        Json(input): Json<UserBody<ResetPasswordInput>>,
+    ) -> Result<Json<String>, AppError> {
+        let UserBody {
+            user: ResetPasswordInput { token, password },
+        } = input;
+
+        let reset_token = prisma
+            .password_reset_token()
+            .find_unique(password_reset_token::token_hash::equals(
+                TokenService::hash_token(&token),
+            ))
+            .exec()
+            .await?
+            .filter(|reset_token| {
+                reset_token.used_at.is_none() && reset_token.expires_at > chrono::Utc::now()
+            })
+            .ok_or(AppError::BadRequest(String::from(
+                "Invalid or expired password reset token",
+            )))?;
+
+        let data = prisma
+            .user()
+            .find_unique(user::id::equals(reset_token.user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        PasswordPolicy::validate(&ctx, &password, &data.username, &data.email)?;
+
+        // Consuming every outstanding token also guards against the same token being used twice
+        let consumed = prisma
+            .password_reset_token()
+            .update_many(
+                vec![
+                    password_reset_token::user_id::equals(reset_token.user_id),
+                    password_reset_token::used_at::equals(None),
+                ],
+                vec![password_reset_token::used_at::set(Some(
+                    chrono::Utc::now().into(),
+                ))],
+            )
+            .exec()
+            .await?;
+
+        if consumed == 0 {
+            return Err(AppError::BadRequest(String::from(
+                "Invalid or expired password reset token",
+            )));
+        }
+
+        prisma
+            .user()
+            .update(
+                user::id::equals(reset_token.user_id),
+                vec![user::password::set(Some(
+                    PasswordHashing::hash(&ctx, &password).await?,
+                ))],
+            )
+            .exec()
+            .await?;
+
+        // A reset usually means the account was at risk, so tokens go along with sessions
+        TokenService::revoke_all_sessions(&prisma, reset_token.user_id).await?;
+        TokenService::revoke_access_tokens(&prisma, reset_token.user_id).await?;
+
+        AuditService::record(
+            &prisma,
+            &client,
+            AuditAction::PasswordChanged,
+            Some(reset_token.user_id),
+            None,
+            Some("password reset"),
+        )
+        .await;
+
+        Ok(Json::from("Password has been reset".to_string()))
+    }
+
+    pub async fn verify_email(
+        prisma: Prisma,
+        client: ClientInfo,
+        Json(input): Json<UserBody<VerifyEmailInput>>,
+    ) -> Result<Json<String>, AppError> {
+        let UserBody {
+            user: VerifyEmailInput { token },
+        } = input;
+
+        let verification = prisma
+            .email_verification_token()
+            .find_unique(email_verification_token::token_hash::equals(
+                TokenService::hash_token(&token),
+            ))
+            .exec()
+            .await?
+            .filter(|verification| {
+                verification.used_at.is_none() && verification.expires_at > chrono::Utc::now()
+            })
+            .ok_or(AppError::BadRequest(String::from(
+                "Invalid or expired email verification token",
+            )))?;
+
+        let consumed = prisma
+            .email_verification_token()
+            .update_many(
+                vec![
+                    email_verification_token::id::equals(verification.id),
+                    email_verification_token::used_at::equals(None),
+                ],
+                vec![email_verification_token::used_at::set(Some(
+                    chrono::Utc::now().into(),
+                ))],
+            )
+            .exec()
+            .await?;
+
+        if consumed == 0 {
+            return Err(AppError::BadRequest(String::from(
+                "Invalid or expired email verification token",
+            )));
+        }
+
+        let data = prisma
+            .user()
+            .find_unique(user::id::equals(verification.user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        let email_changed = verification.email != data.email;
+
+        let params = if !email_changed {
+            vec![user::email_verified::set(true)]
+        } else if data.pending_email.as_deref() == Some(verification.email.as_str()) {
+            vec![
+                user::email::set(verification.email.clone()),
+                user::pending_email::set(None),
+                user::email_verified::set(true),
+            ]
+        } else {
+            // The user changed their email again after this token was sent
+            return Err(AppError::BadRequest(String::from(
+                "Email verification token is no longer valid",
+            )));
+        };
+
+        prisma
+            .user()
+            .update(user::id::equals(data.id), params)
+            .exec()
+            .await?;
+
+        if email_changed {
+            AuditService::record(
+                &prisma,
+                &client,
+                AuditAction::EmailChanged,
+                Some(data.id),
+                None,
+                Some(&verification.email),
+            )
+            .await;
+        }
+
+        Ok(Json::from("Email verified".to_string()))
+    }
+
+    pub async fn resend_verification_email(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        ctx: State<AppContext>,
+    ) -> Result<Json<String>, AppError> {
+        auth_user.require_scope(Scope::ProfileWrite)?;
+
+        let data = prisma
+            .user()
+            .find_unique(user::id::equals(auth_user.user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        let email = match data.pending_email {
+            Some(pending_email) => pending_email,
+            None if !data.email_verified => data.email,
+            None => {
+                return Err(AppError::BadRequest(String::from(
+                    "Email is already verified",
+                )))
+            }
+        };
+
+        Self::send_verification_email(&prisma, &ctx, &data.username, data.id, email).await?;
+
+        Ok(Json::from("Verification email sent".to_string()))
+    }
+
+    /// Fails with 403 when verified emails are required and the user hasn't verified theirs.
+    pub async fn check_email_verified(
+        prisma: &Prisma,
+        ctx: &AppContext,
+        auth_user: &AuthUser,
+    ) -> Result<(), AppError> {
+        if !ctx.config.auth.require_verified_email {
+            return Ok(());
+        }
+
+        let data = prisma
+            .user()
+            .find_unique(user::id::equals(auth_user.user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))?;
+
+        if !data.email_verified {
+            return Err(AppError::Forbidden(String::from(
+                "Verify your email address before publishing",
+            )));
+        }
+
         Ok(())
     }
 
-    fn hash_password(password: &str) -> anyhow::Result<String> {
-        let salt = SaltString::generate(&mut OsRng);
+    async fn send_verification_email(
+        prisma: &Prisma,
+        ctx: &AppContext,
+        username: &str,
+        user_id: i32,
+        email: String,
+    ) -> Result<(), AppError> {
+        let token = TokenService::random_string(64);
+        let expires_at = chrono::Utc::now()
+            + chrono::Duration::seconds(ctx.config.auth.email_verification_exp_in_sec);
+
+        prisma
+            .email_verification_token()
+            .create(
+                TokenService::hash_token(&token),
+                email.clone(),
+                expires_at.into(),
+                user::id::equals(user_id),
+                vec![],
+            )
+            .exec()
+            .await?;
 
-        // Argon2 with default params (Argon2id v19)
-        let argon2 = Argon2::default();
+        let email = Email {
+            to: email,
+            subject: String::from("Confirm your email address"),
+            body: format!(
+                "Hi {},\n\nPlease confirm your email address by opening the link below.\n\n{}/verify-email?token={}\n",
+                username, ctx.config.auth.app_url, token,
+            ),
+        };
 
-        // Hash password to PHC string ($argon2id$v=19$...)
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|_| anyhow::anyhow!("failed to hash password"))?;
+        if let Err(e) = ctx.mailer.send(email).await {
+            error!("failed to send verification email: {:?}", e);
+        }
 
-        Ok(password_hash.to_string())
+        Ok(())
+    }
+
+    async fn send_password_reset_email(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        data: user::Data,
+    ) -> Result<(), AppError> {
+        let token = TokenService::random_string(64);
+        let expires_at = chrono::Utc::now()
+            + chrono::Duration::seconds(ctx.config.auth.password_reset_exp_in_sec);
+
+        prisma
+            .password_reset_token()
+            .create(
+                TokenService::hash_token(&token),
+                expires_at.into(),
+                user::id::equals(data.id),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        let email = Email {
+            to: data.email,
+            subject: String::from("Reset your Conduit password"),
+            body: format!(
+                "Hi {},\n\nUse the link below to choose a new password. It expires in {} minutes.\n\n{}/reset-password?token={}\n\nIf you didn't ask for this, you can ignore this email.\n",
+                data.username,
+                ctx.config.auth.password_reset_exp_in_sec / 60,
+                ctx.config.auth.app_url,
+                token,
+            ),
+        };
+
+        ctx.mailer.send(email).await?;
+
+        Ok(())
     }
 
-    fn verify_password(password: &str, password_hash: &str) -> anyhow::Result<()> {
-        let argon2 = Argon2::default();
-        // Parse password hash from PHC string
-        let password_hash = PasswordHash::new(password_hash)
-            .map_err(|_| anyhow::anyhow!("failed to parse password hash from PHC string"))?;
-        // Verify password against hash
-        argon2
-            .verify_password(password.as_bytes(), &password_hash)
-            .map_err(|_| anyhow::anyhow!("failed to verify password"))?;
+    async fn login_failed<T>(
+        prisma: &Prisma,
+        ctx: &AppContext,
+        keys: &[String],
+    ) -> Result<T, AppError> {
+        for key in keys {
+            LoginThrottleService::record_failure(prisma, ctx, key).await?;
+        }
+
+        Err(AppError::Unauthorized(String::from("Invalid credentials")))
+    }
+
+    /// Rejects an address another account already uses, before it is sent a verification link.
+    async fn check_email_available(prisma: &Prisma, email: &str) -> Result<(), AppError> {
+        let holder = prisma
+            .user()
+            .find_unique(user::email::equals(email.to_string()))
+            .exec()
+            .await?;
+
+        if holder.is_some() {
+            let mut errors = ValidationErrors::default();
+            errors.add("email", "has already been taken");
+            return Err(AppError::Validation(errors));
+        }
+
+        Ok(())
+    }
+
+    fn is_valid_email(email: &str) -> Result<(), AppError> {
+        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
+        if !email_regex.is_match(email) {
+            return Err(AppError::BadRequest(String::from("Invalid email format")));
+        }
         Ok(())
     }
 }
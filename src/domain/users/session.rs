@@ -0,0 +1,66 @@
+use axum::{extract::Path, Extension, Json};
+use prisma_client_rust::Direction;
+use std::sync::Arc;
+
+use crate::{
+    app_error::AppError,
+    extractor::AuthUser,
+    prisma::{session, PrismaClient},
+};
+
+use super::{response::Session, token::TokenService, SessionsBody};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+pub struct SessionsService;
+
+impl SessionsService {
+    pub async fn get_sessions(
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<SessionsBody<Session>>, AppError> {
+        let current_session_id = auth_user.require_session()?;
+
+        let sessions = prisma
+            .session()
+            .find_many(vec![
+                session::user_id::equals(auth_user.user_id),
+                TokenService::active_session_filter(),
+            ])
+            .order_by(session::last_seen_at::order(Direction::Desc))
+            .exec()
+            .await?;
+
+        let sessions = sessions
+            .into_iter()
+            .map(|session| {
+                let current = session.id == current_session_id;
+                session.to_session(current)
+            })
+            .collect();
+
+        Ok(Json::from(SessionsBody { sessions }))
+    }
+
+    pub async fn delete_session(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        Path(session_id): Path<String>,
+    ) -> Result<Json<String>, AppError> {
+        auth_user.require_session()?;
+
+        let session = prisma
+            .session()
+            .find_first(vec![
+                session::id::equals(session_id),
+                session::user_id::equals(auth_user.user_id),
+            ])
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("Session not found")))?;
+
+        TokenService::revoke_session(&prisma, &session.id).await?;
+
+        Ok(Json::from("Session revoked".to_string()))
+    }
+}
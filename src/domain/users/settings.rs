@@ -0,0 +1,227 @@
+use axum::{Extension, Json};
+use prisma_client_rust::{not, or};
+use regex::Regex;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::{
+    app_error::{AppError, ValidationErrors},
+    domain::access_tokens::scope::Scope,
+    extractor::AuthUser,
+    prisma::{article, article_tag, user, user_settings, FeedSort, PrismaClient},
+};
+
+use super::{
+    request::{ContentFiltersInput, EmailNotificationsInput, SettingsUpdateInput},
+    response::Settings,
+    SettingsBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+/// Upper bound on hidden tags and on muted words, each.
+const MAX_FILTER_ENTRIES: usize = 100;
+const MAX_FILTER_LENGTH: usize = 64;
+
+pub struct SettingsService;
+
+impl SettingsService {
+    pub async fn get_settings(
+        auth_user: AuthUser,
+        prisma: Prisma,
+    ) -> Result<Json<SettingsBody<Settings>>, AppError> {
+        auth_user.require_scope(Scope::ProfileRead)?;
+
+        let data = Self::load(&prisma, auth_user.user_id).await?;
+
+        Ok(Json::from(SettingsBody {
+            settings: data.into(),
+        }))
+    }
+
+    pub async fn update_settings(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        Json(input): Json<SettingsBody<SettingsUpdateInput>>,
+    ) -> Result<Json<SettingsBody<Settings>>, AppError> {
+        let SettingsBody {
+            settings:
+                SettingsUpdateInput {
+                    locale,
+                    timezone,
+                    feed_sort,
+                    email_notifications,
+                    content_filters,
+                },
+        } = input;
+
+        auth_user.require_scope(Scope::ProfileWrite)?;
+
+        let mut errors = ValidationErrors::default();
+        let mut params = Vec::new();
+
+        if let Some(locale) = locale {
+            // Language with optional subtags, as in `en`, `pt-BR` or `zh-Hant-TW`
+            let locale_regex = Regex::new(r"^[a-zA-Z]{2,3}(-[a-zA-Z0-9]{2,8})*$").unwrap();
+            if locale_regex.is_match(&locale) {
+                params.push(user_settings::locale::set(locale));
+            } else {
+                errors.add("locale", "is invalid");
+            }
+        }
+
+        if let Some(timezone) = timezone {
+            if timezone.parse::<chrono_tz::Tz>().is_ok() {
+                params.push(user_settings::timezone::set(timezone));
+            } else {
+                errors.add(
+                    "timezone",
+                    "must be an IANA time zone such as Europe/Berlin",
+                );
+            }
+        }
+
+        if let Some(feed_sort) = feed_sort {
+            params.push(user_settings::feed_sort::set(feed_sort));
+        }
+
+        if let Some(EmailNotificationsInput {
+            new_follower,
+            follow_request,
+            comment,
+            favorite,
+        }) = email_notifications
+        {
+            params.extend(new_follower.map(user_settings::email_on_follower::set));
+            params.extend(follow_request.map(user_settings::email_on_follow_request::set));
+            params.extend(comment.map(user_settings::email_on_comment::set));
+            params.extend(favorite.map(user_settings::email_on_favorite::set));
+        }
+
+        if let Some(ContentFiltersInput {
+            hidden_tags,
+            muted_words,
+        }) = content_filters
+        {
+            if let Some(hidden_tags) = hidden_tags {
+                match Self::normalize_filter(hidden_tags) {
+                    Ok(hidden_tags) => {
+                        params.push(user_settings::hidden_tags::set(json!(hidden_tags)))
+                    }
+                    Err(message) => errors.add("hiddenTags", message),
+                }
+            }
+
+            if let Some(muted_words) = muted_words {
+                match Self::normalize_filter(muted_words) {
+                    Ok(muted_words) => {
+                        params.push(user_settings::muted_words::set(json!(muted_words)))
+                    }
+                    Err(message) => errors.add("mutedWords", message),
+                }
+            }
+        }
+
+        errors.into_result()?;
+
+        // Makes sure the row exists before updating it
+        Self::load(&prisma, auth_user.user_id).await?;
+
+        let data = prisma
+            .user_settings()
+            .update(user_settings::user_id::equals(auth_user.user_id), params)
+            .exec()
+            .await?;
+
+        Ok(Json::from(SettingsBody {
+            settings: data.into(),
+        }))
+    }
+
+    /// Settings of a user, created with the defaults on first access.
+    pub async fn load(
+        prisma: &PrismaClient,
+        user_id: i32,
+    ) -> Result<user_settings::Data, AppError> {
+        let data = prisma
+            .user_settings()
+            .upsert(
+                user_settings::user_id::equals(user_id),
+                user_settings::create(user::id::equals(user_id), json!([]), json!([]), vec![]),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Settings of a user without creating them, for read paths that can use the defaults.
+    pub async fn find(
+        prisma: &PrismaClient,
+        user_id: i32,
+    ) -> Result<Option<user_settings::Data>, AppError> {
+        let data = prisma
+            .user_settings()
+            .find_unique(user_settings::user_id::equals(user_id))
+            .exec()
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Leaves out articles with a hidden tag or a muted word in their title or description.
+    pub fn article_filters(data: &user_settings::Data) -> Vec<article::WhereParam> {
+        let hidden_tags: Vec<String> =
+            serde_json::from_value(data.hidden_tags.clone()).unwrap_or_default();
+        let muted_words: Vec<String> =
+            serde_json::from_value(data.muted_words.clone()).unwrap_or_default();
+
+        let mut filter = Vec::new();
+
+        if !hidden_tags.is_empty() {
+            filter.push(article::tags::none(vec![article_tag::tag::in_vec(
+                hidden_tags,
+            )]));
+        }
+
+        for word in muted_words {
+            filter.push(not![or![
+                article::title::contains(word.clone()),
+                article::description::contains(word),
+            ]]);
+        }
+
+        filter
+    }
+
+    /// Feed order chosen by the user, newest first for users without settings.
+    pub fn feed_sort(data: Option<&user_settings::Data>) -> FeedSort {
+        data.map_or(FeedSort::Latest, |data| data.feed_sort)
+    }
+
+    /// Trims and de-duplicates filter entries, dropping empty ones.
+    fn normalize_filter(entries: Vec<String>) -> Result<Vec<String>, String> {
+        let mut normalized: Vec<String> = Vec::new();
+
+        for entry in entries {
+            let entry = entry.trim();
+            if entry.is_empty() || normalized.iter().any(|other| other == entry) {
+                continue;
+            }
+            if entry.chars().count() > MAX_FILTER_LENGTH {
+                return Err(format!(
+                    "entries must be at most {} characters long",
+                    MAX_FILTER_LENGTH
+                ));
+            }
+            normalized.push(entry.to_string());
+        }
+
+        if normalized.len() > MAX_FILTER_ENTRIES {
+            return Err(format!("must have at most {} entries", MAX_FILTER_ENTRIES));
+        }
+
+        Ok(normalized)
+    }
+}
@@ -0,0 +1,132 @@
+use prisma_client_rust::{chrono, or};
+use tracing::warn;
+
+use crate::{
+    app_error::AppError,
+    config::AppContext,
+    prisma::{lockout_event, login_throttle, PrismaClient},
+};
+
+pub struct LoginThrottleService;
+
+impl LoginThrottleService {
+    pub fn account_key(email: &str) -> String {
+        format!("account:{}", email.trim().to_lowercase())
+    }
+
+    pub fn ip_key(ip: &str) -> String {
+        format!("ip:{}", ip)
+    }
+
+    /// Rejects the login attempt while any of the keys is locked out.
+    pub async fn check(prisma: &PrismaClient, keys: &[String]) -> Result<(), AppError> {
+        let locked = prisma
+            .login_throttle()
+            .find_first(vec![
+                login_throttle::key::in_vec(keys.to_vec()),
+                login_throttle::locked_until::gt(chrono::Utc::now().into()),
+            ])
+            .exec()
+            .await?;
+
+        if locked.is_some() {
+            return Err(AppError::TooManyRequests(String::from(
+                "Too many failed login attempts, try again later",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Counts a failed attempt, locking the key out once the threshold is reached.
+    ///
+    /// Each failure past the threshold doubles the lockout. Counters that stayed quiet
+    /// for longer than the maximum lockout start over.
+    pub async fn record_failure(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        key: &str,
+    ) -> Result<(), AppError> {
+        let auth = &ctx.config.auth;
+        let now = chrono::Utc::now();
+
+        prisma
+            .login_throttle()
+            .update_many(
+                vec![
+                    login_throttle::key::equals(key.to_string()),
+                    login_throttle::updated_at::lt(
+                        (now - chrono::Duration::seconds(auth.login_lockout_max_in_sec)).into(),
+                    ),
+                ],
+                vec![login_throttle::failures::set(0)],
+            )
+            .exec()
+            .await?;
+
+        // Incremented in the database, so concurrent failures can't overwrite each other
+        let throttle = prisma
+            .login_throttle()
+            .upsert(
+                login_throttle::key::equals(key.to_string()),
+                login_throttle::create(key.to_string(), vec![login_throttle::failures::set(1)]),
+                vec![login_throttle::failures::increment(1)],
+            )
+            .exec()
+            .await?;
+        let failures = throttle.failures;
+
+        let locked_until = (failures >= auth.login_lockout_threshold).then(|| {
+            let exponent = (failures - auth.login_lockout_threshold).min(20) as u32;
+            let lockout = auth
+                .login_lockout_base_in_sec
+                .saturating_mul(2i64.pow(exponent))
+                .min(auth.login_lockout_max_in_sec);
+
+            now + chrono::Duration::seconds(lockout)
+        });
+
+        if let Some(locked_until) = locked_until {
+            // A concurrent failure may already have set a longer lockout
+            prisma
+                .login_throttle()
+                .update_many(
+                    vec![
+                        login_throttle::key::equals(key.to_string()),
+                        or![
+                            login_throttle::locked_until::equals(None),
+                            login_throttle::locked_until::lt(locked_until.into()),
+                        ],
+                    ],
+                    vec![login_throttle::locked_until::set(Some(locked_until.into()))],
+                )
+                .exec()
+                .await?;
+        }
+
+        if let Some(locked_until) = locked_until {
+            warn!(
+                "locking out {} after {} failed logins until {}",
+                key, failures, locked_until
+            );
+
+            prisma
+                .lockout_event()
+                .create(key.to_string(), failures, locked_until.into(), vec![])
+                .exec()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn reset(prisma: &PrismaClient, key: &str) -> Result<(), AppError> {
+        prisma
+            .login_throttle()
+            .delete_many(vec![login_throttle::key::equals(key.to_string())])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+}
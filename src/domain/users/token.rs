@@ -0,0 +1,395 @@
+use prisma_client_rust::chrono;
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_error::AppError,
+    config::AppContext,
+    domain::{access_tokens::scope::Scope, audit::service::AuditService},
+    extractor::{AuthUser, ClientInfo},
+    prisma::{
+        personal_access_token, refresh_token, session, two_factor_challenge, user, AuditAction,
+        PrismaClient,
+    },
+};
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+const SESSION_ID_LENGTH: usize = 32;
+const CHALLENGE_EXP_IN_SEC: i64 = 300;
+const CHALLENGE_ID_LENGTH: usize = 32;
+const CHALLENGE_PURPOSE: &str = "2fa";
+const LAST_SEEN_RESOLUTION_IN_SEC: i64 = 300;
+
+pub struct IssuedTokens {
+    pub user_id: i32,
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ChallengeClaims {
+    pub user_id: i32,
+    /// Id of the database row that makes the challenge single-use.
+    pub jti: String,
+    purpose: String,
+    exp: i64,
+}
+
+pub struct TokenService;
+
+impl TokenService {
+    /// Starts a new session for the user and issues its first access/refresh token pair.
+    pub async fn issue(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        user_id: i32,
+        client: &ClientInfo,
+    ) -> Result<IssuedTokens, AppError> {
+        let session = prisma
+            .session()
+            .create(
+                Self::random_string(SESSION_ID_LENGTH),
+                user::id::equals(user_id),
+                vec![
+                    session::user_agent::set(client.user_agent.clone()),
+                    session::ip::set(Some(client.ip.clone())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        AuditService::record(
+            prisma,
+            client,
+            AuditAction::TokenIssued,
+            Some(user_id),
+            None,
+            Some("session"),
+        )
+        .await;
+
+        Self::issue_for_session(prisma, ctx, user_id, session.id).await
+    }
+
+    /// Exchanges a refresh token for a new token pair in the same session.
+    ///
+    /// Every refresh token can be used once. Presenting a token that was already
+    /// rotated revokes the whole session, since it means the token has leaked.
+    pub async fn rotate(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        token: &str,
+    ) -> Result<IssuedTokens, AppError> {
+        let data = prisma
+            .refresh_token()
+            .find_unique(refresh_token::token_hash::equals(Self::hash_token(token)))
+            .exec()
+            .await?
+            .ok_or(AppError::Unauthorized(String::from("Invalid refresh token")))?;
+
+        if data.revoked_at.is_some() {
+            Self::revoke_session(prisma, &data.session_id).await?;
+
+            return Err(AppError::Unauthorized(String::from(
+                "Refresh token has been revoked",
+            )));
+        }
+
+        if data.expires_at < chrono::Utc::now() {
+            return Err(AppError::Unauthorized(String::from(
+                "Refresh token is expired",
+            )));
+        }
+
+        let session_id = data.session_id.clone();
+
+        // Revoking the old token and storing its successor succeed or fail together, so a
+        // failed rotation leaves the old token usable instead of looking like reuse later
+        let issued = prisma
+            ._transaction()
+            .run(|tx| async move {
+                let revoked = tx
+                    .refresh_token()
+                    .update_many(
+                        vec![
+                            refresh_token::id::equals(data.id),
+                            refresh_token::revoked_at::equals(None),
+                        ],
+                        vec![refresh_token::revoked_at::set(Some(
+                            chrono::Utc::now().into(),
+                        ))],
+                    )
+                    .exec()
+                    .await?;
+
+                if revoked == 0 {
+                    return Ok(None);
+                }
+
+                Self::issue_for_session(&tx, ctx, data.user_id, data.session_id)
+                    .await
+                    .map(Some)
+            })
+            .await?;
+
+        // Another request rotated this token first
+        let Some(issued) = issued else {
+            Self::revoke_session(prisma, &session_id).await?;
+
+            return Err(AppError::Unauthorized(String::from(
+                "Refresh token has been revoked",
+            )));
+        };
+
+        Ok(issued)
+    }
+
+    /// Deletes the session together with its refresh tokens.
+    pub async fn revoke_session(prisma: &PrismaClient, session_id: &str) -> Result<(), AppError> {
+        prisma
+            .refresh_token()
+            .delete_many(vec![refresh_token::session_id::equals(
+                session_id.to_string(),
+            )])
+            .exec()
+            .await?;
+
+        prisma
+            .session()
+            .delete_many(vec![session::id::equals(session_id.to_string())])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Also drops pending two-factor challenges, so no login started before can finish.
+    pub async fn revoke_all_sessions(prisma: &PrismaClient, user_id: i32) -> Result<(), AppError> {
+        prisma
+            .two_factor_challenge()
+            .delete_many(vec![two_factor_challenge::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .refresh_token()
+            .delete_many(vec![refresh_token::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        prisma
+            .session()
+            .delete_many(vec![session::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes every personal access token of the user. They outlive sessions, so a
+    /// compromised account isn't safe again until they are gone too.
+    pub async fn revoke_access_tokens(prisma: &PrismaClient, user_id: i32) -> Result<(), AppError> {
+        prisma
+            .personal_access_token()
+            .delete_many(vec![personal_access_token::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Filter matching sessions that still hold an unrevoked, unexpired refresh token.
+    pub fn active_session_filter() -> session::WhereParam {
+        session::refresh_tokens::some(vec![
+            refresh_token::revoked_at::equals(None),
+            refresh_token::expires_at::gt(chrono::Utc::now().into()),
+        ])
+    }
+
+    /// Checks that the session still exists and bumps its last-seen time.
+    pub async fn is_session_active(
+        prisma: &PrismaClient,
+        session_id: &str,
+    ) -> Result<bool, AppError> {
+        let data = prisma
+            .session()
+            .find_first(vec![
+                session::id::equals(session_id.to_string()),
+                Self::active_session_filter(),
+            ])
+            .exec()
+            .await?;
+
+        let Some(data) = data else {
+            return Ok(false);
+        };
+
+        // Only write once in a while so every request doesn't cost an update
+        let now = chrono::Utc::now();
+        if data.last_seen_at < now - chrono::Duration::seconds(LAST_SEEN_RESOLUTION_IN_SEC) {
+            prisma
+                .session()
+                .update_many(
+                    vec![session::id::equals(data.id)],
+                    vec![session::last_seen_at::set(now.into())],
+                )
+                .exec()
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Issues a short-lived token proving the password step of a two-factor login succeeded.
+    ///
+    /// It carries no session, so it can't be used as an access token. A database row backs
+    /// every challenge so it can be redeemed only once.
+    pub async fn issue_challenge(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        user_id: i32,
+    ) -> Result<String, AppError> {
+        let now = chrono::Utc::now();
+        let jti = Self::random_string(CHALLENGE_ID_LENGTH);
+
+        // Abandoned challenges are cleaned up whenever a new one is issued
+        prisma
+            .two_factor_challenge()
+            .delete_many(vec![two_factor_challenge::expires_at::lt(now.into())])
+            .exec()
+            .await?;
+
+        prisma
+            .two_factor_challenge()
+            .create(
+                jti.clone(),
+                (now + chrono::Duration::seconds(CHALLENGE_EXP_IN_SEC)).into(),
+                user::id::equals(user_id),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        let key = ctx.config.jwt.active_key();
+        let claims = ChallengeClaims {
+            user_id,
+            jti,
+            purpose: CHALLENGE_PURPOSE.to_string(),
+            exp: now.timestamp() + CHALLENGE_EXP_IN_SEC,
+        };
+
+        let mut header = jsonwebtoken::Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+
+        Ok(jsonwebtoken::encode(&header, &claims, key.encoding_key.as_ref().unwrap()).unwrap())
+    }
+
+    /// Marks the challenge as used. Fails when it was redeemed or revoked before.
+    pub async fn consume_challenge(prisma: &PrismaClient, jti: &str) -> Result<(), AppError> {
+        let consumed = prisma
+            .two_factor_challenge()
+            .delete_many(vec![
+                two_factor_challenge::jti::equals(jti.to_string()),
+                two_factor_challenge::expires_at::gt(chrono::Utc::now().into()),
+            ])
+            .exec()
+            .await?;
+
+        if consumed == 0 {
+            return Err(AppError::Unauthorized(String::from(
+                "Invalid or expired challenge token",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the claims of a valid challenge token that has not been redeemed yet.
+    pub async fn verify_challenge(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        token: &str,
+    ) -> Result<ChallengeClaims, AppError> {
+        let invalid = || AppError::Unauthorized(String::from("Invalid or expired challenge token"));
+
+        let header = jsonwebtoken::decode_header(token).map_err(|_| invalid())?;
+        let key = ctx
+            .config
+            .jwt
+            .verification_key(header.kid.as_deref())
+            .ok_or_else(invalid)?;
+
+        let claims = jsonwebtoken::decode::<ChallengeClaims>(
+            token,
+            &key.decoding_key,
+            &jsonwebtoken::Validation::new(key.algorithm),
+        )
+        .map_err(|_| invalid())?
+        .claims;
+
+        if claims.purpose != CHALLENGE_PURPOSE {
+            return Err(invalid());
+        }
+
+        let pending = prisma
+            .two_factor_challenge()
+            .find_unique(two_factor_challenge::jti::equals(claims.jti.clone()))
+            .exec()
+            .await?;
+
+        match pending {
+            Some(pending) if pending.user_id == claims.user_id => Ok(claims),
+            _ => Err(invalid()),
+        }
+    }
+
+    async fn issue_for_session(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        user_id: i32,
+        session_id: String,
+    ) -> Result<IssuedTokens, AppError> {
+        let refresh_token = Self::random_string(REFRESH_TOKEN_LENGTH);
+        let expires_at =
+            chrono::Utc::now() + chrono::Duration::seconds(ctx.config.jwt.refresh_exp_in_sec);
+
+        prisma
+            .refresh_token()
+            .create(
+                Self::hash_token(&refresh_token),
+                expires_at.into(),
+                user::id::equals(user_id),
+                session::id::equals(session_id.clone()),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        let access_token = AuthUser {
+            user_id,
+            session_id: Some(session_id),
+            scopes: Scope::ALL.to_vec(),
+        }
+        .to_jwt(ctx)
+        .expect("session callers always get a JWT");
+
+        Ok(IssuedTokens {
+            user_id,
+            access_token,
+            refresh_token,
+        })
+    }
+
+    pub(crate) fn random_string(len: usize) -> String {
+        OsRng
+            .sample_iter(&Alphanumeric)
+            .take(len)
+            .map(char::from)
+            .collect()
+    }
+
+    // Only the SHA-256 of a token is stored, so a database leak can't be replayed
+    pub(crate) fn hash_token(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+}
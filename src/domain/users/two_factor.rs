@@ -0,0 +1,350 @@
+use axum::{extract::State, Extension, Json};
+use prisma_client_rust::{chrono, or};
+use rand::{rngs::OsRng, RngCore};
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{
+    app_error::AppError,
+    config::AppContext,
+    extractor::AuthUser,
+    prisma::{recovery_code, user, PrismaClient},
+};
+
+use super::{
+    account::AccountService,
+    password::PasswordHashing,
+    request::{TwoFactorCodeInput, TwoFactorDisableInput},
+    response::{RecoveryCodes, TwoFactorEnrollment},
+    token::TokenService,
+    TwoFactorBody,
+};
+
+type Prisma = Extension<Arc<PrismaClient>>;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+pub struct TwoFactorService;
+
+impl TwoFactorService {
+    pub async fn enroll(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        ctx: State<AppContext>,
+    ) -> Result<Json<TwoFactorBody<TwoFactorEnrollment>>, AppError> {
+        auth_user.require_session()?;
+
+        let data = Self::find_user(&prisma, auth_user.user_id).await?;
+
+        if data.totp_enabled {
+            return Err(AppError::BadRequest(String::from(
+                "Two-factor authentication is already enabled",
+            )));
+        }
+
+        let mut secret = [0u8; 20];
+        OsRng.fill_bytes(&mut secret);
+        let secret = Secret::Raw(secret.to_vec()).to_encoded().to_string();
+
+        let totp = Self::totp(&ctx, &secret, &data.email)?;
+
+        prisma
+            .user()
+            .update(
+                user::id::equals(data.id),
+                vec![
+                    user::totp_secret::set(Some(secret.clone())),
+                    user::totp_last_step::set(None),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Ok(Json::from(TwoFactorBody {
+            two_factor: TwoFactorEnrollment {
+                secret,
+                otpauth_uri: totp.get_url(),
+            },
+        }))
+    }
+
+    /// Enables two-factor authentication once the user proves their authenticator works.
+    pub async fn confirm(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        Json(input): Json<TwoFactorBody<TwoFactorCodeInput>>,
+    ) -> Result<Json<TwoFactorBody<RecoveryCodes>>, AppError> {
+        let TwoFactorBody {
+            two_factor: TwoFactorCodeInput { code },
+        } = input;
+
+        auth_user.require_session()?;
+
+        let data = Self::find_user(&prisma, auth_user.user_id).await?;
+
+        if data.totp_enabled {
+            return Err(AppError::BadRequest(String::from(
+                "Two-factor authentication is already enabled",
+            )));
+        }
+
+        if data.totp_secret.is_none() {
+            return Err(AppError::BadRequest(String::from(
+                "Two-factor enrollment has not been started",
+            )));
+        }
+
+        if !Self::accept_totp(&prisma, &ctx, &data, &code).await? {
+            return Err(AppError::BadRequest(String::from(
+                "Invalid two-factor code",
+            )));
+        }
+
+        prisma
+            .user()
+            .update(
+                user::id::equals(data.id),
+                vec![user::totp_enabled::set(true)],
+            )
+            .exec()
+            .await?;
+
+        let recovery_codes = Self::regenerate_recovery_codes(&prisma, data.id).await?;
+
+        Ok(Json::from(TwoFactorBody {
+            two_factor: RecoveryCodes { recovery_codes },
+        }))
+    }
+
+    pub async fn disable(
+        auth_user: AuthUser,
+        prisma: Prisma,
+        ctx: State<AppContext>,
+        Json(input): Json<TwoFactorBody<TwoFactorDisableInput>>,
+    ) -> Result<Json<String>, AppError> {
+        let TwoFactorBody {
+            two_factor: TwoFactorDisableInput { password, code },
+        } = input;
+
+        let session_id = auth_user.require_session()?;
+
+        let data = Self::find_user(&prisma, auth_user.user_id).await?;
+
+        if !data.totp_enabled {
+            return Err(AppError::BadRequest(String::from(
+                "Two-factor authentication is not enabled",
+            )));
+        }
+
+        match data.password.as_deref() {
+            Some(password_hash) => {
+                let verified = match &password {
+                    Some(password) => PasswordHashing::verify(&ctx, password, password_hash)
+                        .await
+                        .is_match(),
+                    None => false,
+                };
+
+                if !verified {
+                    return Err(AppError::Unauthorized(String::from("Invalid password")));
+                }
+            }
+            None => {
+                if !AccountService::is_recent_login(&prisma, session_id).await? {
+                    return Err(AppError::Unauthorized(String::from(
+                        "Log in again to disable two-factor authentication",
+                    )));
+                }
+            }
+        }
+
+        if !Self::verify_code(&prisma, &ctx, &data, &code).await? {
+            return Err(AppError::Unauthorized(String::from(
+                "Invalid two-factor code",
+            )));
+        }
+
+        prisma
+            .user()
+            .update(
+                user::id::equals(data.id),
+                vec![
+                    user::totp_enabled::set(false),
+                    user::totp_secret::set(None),
+                    user::totp_last_step::set(None),
+                ],
+            )
+            .exec()
+            .await?;
+
+        prisma
+            .recovery_code()
+            .delete_many(vec![recovery_code::user_id::equals(data.id)])
+            .exec()
+            .await?;
+
+        Ok(Json::from("Two-factor authentication disabled".to_string()))
+    }
+
+    /// Accepts either a current TOTP code or an unused recovery code. Both work only once.
+    pub async fn verify_code(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        data: &user::Data,
+        code: &str,
+    ) -> Result<bool, AppError> {
+        let code = code.trim();
+
+        if Self::accept_totp(prisma, ctx, data, code).await? {
+            return Ok(true);
+        }
+
+        let consumed = prisma
+            .recovery_code()
+            .update_many(
+                vec![
+                    recovery_code::user_id::equals(data.id),
+                    recovery_code::code_hash::equals(TokenService::hash_token(code)),
+                    recovery_code::used_at::equals(None),
+                ],
+                vec![recovery_code::used_at::set(Some(chrono::Utc::now().into()))],
+            )
+            .exec()
+            .await?;
+
+        Ok(consumed > 0)
+    }
+
+    async fn regenerate_recovery_codes(
+        prisma: &PrismaClient,
+        user_id: i32,
+    ) -> Result<Vec<String>, AppError> {
+        prisma
+            .recovery_code()
+            .delete_many(vec![recovery_code::user_id::equals(user_id)])
+            .exec()
+            .await?;
+
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| TokenService::random_string(RECOVERY_CODE_LENGTH))
+            .collect();
+
+        prisma
+            .recovery_code()
+            .create_many(
+                codes
+                    .iter()
+                    .map(|code| {
+                        recovery_code::create_unchecked(
+                            TokenService::hash_token(code),
+                            user_id,
+                            vec![],
+                        )
+                    })
+                    .collect(),
+            )
+            .exec()
+            .await?;
+
+        Ok(codes)
+    }
+
+    /// Accepts a TOTP code unless a code of the same or a later time step was accepted
+    /// before, so an observed code can't be replayed while it is still valid.
+    async fn accept_totp(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        data: &user::Data,
+        code: &str,
+    ) -> Result<bool, AppError> {
+        let Some(secret) = &data.totp_secret else {
+            return Ok(false);
+        };
+
+        let Some(step) = Self::totp_step(ctx, secret, &data.email, code)? else {
+            return Ok(false);
+        };
+
+        // Conditional update, so concurrent logins with the same code can't both win
+        let accepted = prisma
+            .user()
+            .update_many(
+                vec![
+                    user::id::equals(data.id),
+                    or![
+                        user::totp_last_step::equals(None),
+                        user::totp_last_step::lt(step),
+                    ],
+                ],
+                vec![user::totp_last_step::set(Some(step))],
+            )
+            .exec()
+            .await?;
+
+        Ok(accepted > 0)
+    }
+
+    /// Time step the code was generated for, allowing for the configured clock skew.
+    fn totp_step(
+        ctx: &AppContext,
+        secret: &str,
+        email: &str,
+        code: &str,
+    ) -> Result<Option<i64>, AppError> {
+        let totp = Self::totp(ctx, secret, email)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| anyhow::anyhow!("system clock error: {}", e))?
+            .as_secs();
+        let current = now / totp.step;
+        let skew = u64::from(totp.skew);
+
+        Ok((current.saturating_sub(skew)..=current + skew)
+            .find(|step| Self::codes_equal(&totp.generate(step * totp.step), code))
+            .map(|step| step as i64))
+    }
+
+    /// Compares in constant time, so response times don't leak matching digits.
+    fn codes_equal(expected: &str, code: &str) -> bool {
+        expected.len() == code.len()
+            && expected
+                .bytes()
+                .zip(code.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    fn totp(ctx: &AppContext, secret: &str, email: &str) -> Result<TOTP, AppError> {
+        let secret = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|e| anyhow::anyhow!("invalid TOTP secret: {:?}", e))?;
+
+        let totp = TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            30,
+            secret,
+            Some(ctx.config.auth.totp_issuer.clone()),
+            email.to_string(),
+        )
+        .map_err(|e| anyhow::anyhow!("failed to build TOTP: {:?}", e))?;
+
+        Ok(totp)
+    }
+
+    async fn find_user(prisma: &PrismaClient, user_id: i32) -> Result<user::Data, AppError> {
+        prisma
+            .user()
+            .find_unique(user::id::equals(user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::NotFound(String::from("User not found")))
+    }
+}
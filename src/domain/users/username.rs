@@ -0,0 +1,293 @@
+use prisma_client_rust::{chrono, Direction};
+
+use crate::{
+    app_error::{AppError, ValidationErrors},
+    config::AppContext,
+    prisma::{user, username_history, PrismaClient},
+};
+
+const MIN_LENGTH: usize = 3;
+const MAX_LENGTH: usize = 32;
+
+/// Names that would be confused with routes, staff or system accounts.
+const RESERVED: &[&str] = &[
+    "admin",
+    "administrator",
+    "api",
+    "anonymous",
+    "conduit",
+    "editor",
+    "feed",
+    "help",
+    "login",
+    "logout",
+    "me",
+    "moderator",
+    "new",
+    "null",
+    "profile",
+    "profiles",
+    "register",
+    "root",
+    "settings",
+    "staff",
+    "support",
+    "system",
+    "undefined",
+    "user",
+    "users",
+];
+
+/// Prefixes used for accounts the server renames itself.
+const RESERVED_PREFIXES: &[&str] = &["deleted-"];
+
+pub struct Username;
+
+impl Username {
+    /// Usernames are unique and looked up by this form, the original casing is only displayed.
+    pub fn canonical(username: &str) -> String {
+        username.trim().to_lowercase()
+    }
+
+    pub fn validate(username: &str) -> Result<(), AppError> {
+        let mut errors = ValidationErrors::default();
+        let length = username.chars().count();
+
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+            errors.add(
+                "username",
+                format!(
+                    "must be between {} and {} characters",
+                    MIN_LENGTH, MAX_LENGTH
+                ),
+            );
+        }
+
+        if !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            errors.add(
+                "username",
+                "may only contain letters, digits, underscores and dashes",
+            );
+        }
+
+        if !username.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            errors.add("username", "must start with a letter or digit");
+        }
+
+        if Self::is_reserved(username) {
+            errors.add("username", "is reserved");
+        }
+
+        errors.into_result()
+    }
+
+    pub fn is_reserved(username: &str) -> bool {
+        let canonical = Self::canonical(username);
+
+        RESERVED.contains(&canonical.as_str())
+            || RESERVED_PREFIXES
+                .iter()
+                .any(|prefix| canonical.starts_with(prefix))
+    }
+
+    /// Rejects names held by another user, or given up by one within the cool-down.
+    ///
+    /// `user_id` is the user taking the name, so people can go back to their own old names.
+    pub async fn check_available(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        username: &str,
+        user_id: Option<i32>,
+    ) -> Result<(), AppError> {
+        if !Self::is_available(prisma, ctx, username, user_id).await? {
+            let mut errors = ValidationErrors::default();
+            errors.add("username", "has already been taken");
+            return Err(AppError::Validation(errors));
+        }
+
+        Ok(())
+    }
+
+    pub async fn is_available(
+        prisma: &PrismaClient,
+        ctx: &AppContext,
+        username: &str,
+        user_id: Option<i32>,
+    ) -> Result<bool, AppError> {
+        let canonical = Self::canonical(username);
+
+        let holder = prisma
+            .user()
+            .find_unique(user::canonical_username::equals(canonical.clone()))
+            .exec()
+            .await?;
+
+        if holder.is_some_and(|holder| Some(holder.id) != user_id) {
+            return Ok(false);
+        }
+
+        let cutoff =
+            chrono::Utc::now() - chrono::Duration::days(ctx.config.auth.username_cooldown_days);
+        let mut filter = vec![
+            username_history::canonical_username::equals(canonical),
+            username_history::created_at::gt(cutoff.into()),
+        ];
+        if let Some(user_id) = user_id {
+            filter.push(username_history::user_id::not(user_id));
+        }
+
+        let locked = prisma.username_history().count(filter).exec().await?;
+
+        Ok(locked == 0)
+    }
+
+    /// Finds the active user holding a username, or else the user who held it last.
+    ///
+    /// Callers compare the canonical username of the result to tell a rename apart.
+    pub async fn resolve(
+        prisma: &PrismaClient,
+        username: &str,
+    ) -> Result<Option<user::Data>, AppError> {
+        let canonical = Self::canonical(username);
+
+        let current = prisma
+            .user()
+            .find_first(vec![
+                user::canonical_username::equals(canonical.clone()),
+                user::deleted_at::equals(None),
+            ])
+            .exec()
+            .await?;
+
+        if current.is_some() {
+            return Ok(current);
+        }
+
+        let previous = prisma
+            .username_history()
+            .find_first(vec![username_history::canonical_username::equals(
+                canonical,
+            )])
+            .with(username_history::user::fetch())
+            .order_by(username_history::id::order(Direction::Desc))
+            .exec()
+            .await?;
+
+        Ok(previous
+            .and_then(|entry| entry.user)
+            .map(|data| *data)
+            .filter(|data| data.deleted_at.is_none()))
+    }
+
+    /// Keeps the name a user gives up, so it still resolves and stays locked for a while.
+    pub async fn record_rename(
+        prisma: &PrismaClient,
+        user_id: i32,
+        previous_username: &str,
+    ) -> Result<(), AppError> {
+        prisma
+            .username_history()
+            .create(
+                previous_username.to_string(),
+                Self::canonical(previous_username),
+                user::id::equals(user_id),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(username: &str) -> Vec<String> {
+        match Username::validate(username) {
+            Ok(()) => Vec::new(),
+            Err(AppError::Validation(errors)) => {
+                let mut errors = serde_json::to_value(errors).unwrap();
+                serde_json::from_value(errors["username"].take()).unwrap()
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn validate_accepts_usual_names() {
+        assert!(errors("jake").is_empty());
+        assert!(errors("Jake_Doe-2").is_empty());
+        assert!(errors("2fast").is_empty());
+        assert!(errors("abc").is_empty());
+        assert!(errors(&"a".repeat(MAX_LENGTH)).is_empty());
+    }
+
+    #[test]
+    fn validate_checks_the_length() {
+        assert_eq!(errors("ab"), ["must be between 3 and 32 characters"]);
+        assert_eq!(
+            errors(&"a".repeat(MAX_LENGTH + 1)),
+            ["must be between 3 and 32 characters"]
+        );
+        assert_eq!(
+            errors(""),
+            [
+                "must be between 3 and 32 characters",
+                "must start with a letter or digit",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_checks_the_characters() {
+        assert_eq!(
+            errors("jake.doe"),
+            ["may only contain letters, digits, underscores and dashes"]
+        );
+        assert_eq!(
+            errors("jäke"),
+            ["may only contain letters, digits, underscores and dashes"]
+        );
+        assert_eq!(
+            errors(" jake"),
+            [
+                "may only contain letters, digits, underscores and dashes",
+                "must start with a letter or digit",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_checks_the_first_character() {
+        assert_eq!(errors("_jake"), ["must start with a letter or digit"]);
+        assert_eq!(errors("-jake"), ["must start with a letter or digit"]);
+    }
+
+    #[test]
+    fn validate_rejects_reserved_names() {
+        assert_eq!(errors("admin"), ["is reserved"]);
+        assert_eq!(errors("Settings"), ["is reserved"]);
+        assert_eq!(errors("deleted-42"), ["is reserved"]);
+    }
+
+    #[test]
+    fn is_reserved_ignores_case_and_matches_prefixes() {
+        assert!(Username::is_reserved("ADMIN"));
+        assert!(Username::is_reserved(" me "));
+        assert!(Username::is_reserved("Deleted-jake"));
+        assert!(!Username::is_reserved("administrators"));
+        assert!(!Username::is_reserved("undeleted-jake"));
+        assert!(!Username::is_reserved("jake"));
+    }
+
+    #[test]
+    fn canonical_trims_and_lowercases() {
+        assert_eq!(Username::canonical("  JaKe "), "jake");
+        assert_eq!(Username::canonical("jake"), "jake");
+    }
+}
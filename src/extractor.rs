@@ -1,43 +1,146 @@
 use axum::{
     async_trait,
-    extract::{FromRef, FromRequestParts},
-    http::{header::AUTHORIZATION, request::Parts, HeaderValue},
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{
+        header::{AUTHORIZATION, USER_AGENT},
+        request::Parts,
+        HeaderValue,
+    },
 };
 use jsonwebtoken::{encode, TokenData};
 use prisma_client_rust::chrono;
+use std::{net::SocketAddr, sync::Arc};
 
 use tracing::{debug, info};
 
-use crate::{app_error::AppError, config::AppContext};
+use crate::{
+    app_error::AppError,
+    config::AppContext,
+    domain::{
+        access_tokens::{
+            scope::Scope,
+            service::{AccessTokensService, ACCESS_TOKEN_PREFIX},
+        },
+        users::{permission::Permission, token::TokenService},
+    },
+    prisma::{user, PrismaClient, Role},
+};
 
 const AUTH_HEADER_PREFIX: &str = "Token ";
+const USER_AGENT_MAX_LENGTH: usize = 191;
+const REQUEST_ID_MAX_LENGTH: usize = 191;
+const REQUEST_ID_HEADER: &str = "x-request-id";
 
+/// The authenticated caller.
+///
+/// Session logins carry a `session_id` and every scope. Personal access tokens have
+/// no session and are limited to the scopes they were created with.
 #[derive(Debug)]
 pub struct AuthUser {
     pub user_id: i32,
+    pub session_id: Option<String>,
+    pub scopes: Vec<Scope>,
 }
 
 #[derive(Debug)]
 pub struct OptionalAuthUser(pub Option<AuthUser>);
 
+/// Describes the client making the request.
+///
+/// The IP is taken from `X-Forwarded-For` when proxy headers are trusted. The request id
+/// is the `X-Request-Id` header, which is generated for requests that come without one.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip: String,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl ClientInfo {
+    /// Picks the address the outermost of `hops` trusted proxies saw from an
+    /// `X-Forwarded-For` value. Entries left of it are made up by the client.
+    fn forwarded_ip(forwarded_for: &str, hops: usize) -> Option<String> {
+        forwarded_for
+            .rsplit(',')
+            .nth(hops.max(1) - 1)
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty())
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 struct AuthUserClaims {
     user_id: i32,
+    sid: String,
     exp: i64,
 }
 
 impl AuthUser {
-    pub fn to_jwt(&self, ctx: &AppContext) -> String {
-        let key = jsonwebtoken::EncodingKey::from_secret(ctx.config.jwt.secret.as_ref());
+    /// Signs a fresh access token for the session. Access token callers have none.
+    pub fn to_jwt(&self, ctx: &AppContext) -> Option<String> {
+        let session_id = self.session_id.clone()?;
+        let key = ctx.config.jwt.active_key();
         let claims = AuthUserClaims {
             user_id: self.user_id,
+            sid: session_id,
             exp: chrono::Utc::now().timestamp() + ctx.config.jwt.exp_in_sec,
         };
 
-        encode(&jsonwebtoken::Header::default(), &claims, &key).unwrap()
+        let mut header = jsonwebtoken::Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+
+        Some(encode(&header, &claims, key.encoding_key.as_ref().unwrap()).unwrap())
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn require_scope(&self, scope: Scope) -> Result<(), AppError> {
+        if !self.has_scope(scope) {
+            return Err(AppError::Forbidden(format!(
+                "Token is missing the {} scope",
+                scope
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Looks up the current role, so role changes apply without a new login.
+    pub async fn role(&self, prisma: &PrismaClient) -> Result<Role, AppError> {
+        let data = prisma
+            .user()
+            .find_unique(user::id::equals(self.user_id))
+            .exec()
+            .await?
+            .ok_or(AppError::Unauthorized(String::from("User not found")))?;
+
+        Ok(data.role)
+    }
+
+    pub async fn has_permission(
+        &self,
+        prisma: &PrismaClient,
+        permission: Permission,
+    ) -> Result<bool, AppError> {
+        Ok(permission.is_granted_to(self.role(prisma).await?))
     }
 
-    fn from_authorization(ctx: &AppContext, auth_header: &HeaderValue) -> Result<Self, AppError> {
+    /// Rejects personal access tokens for account management endpoints.
+    pub fn require_session(&self) -> Result<&str, AppError> {
+        self.session_id
+            .as_deref()
+            .ok_or(AppError::Forbidden(String::from(
+                "This endpoint requires a login session",
+            )))
+    }
+
+    async fn from_authorization(
+        ctx: &AppContext,
+        prisma: &PrismaClient,
+        auth_header: &HeaderValue,
+    ) -> Result<Self, AppError> {
         let auth_header = auth_header.to_str().map_err(|_| {
             info!("Authorization header is not UTF-8");
             AppError::Unauthorized(String::from("Authorization header is not UTF-8"))
@@ -55,10 +158,28 @@ impl AuthUser {
 
         let token = &auth_header[AUTH_HEADER_PREFIX.len()..];
 
+        if token.starts_with(ACCESS_TOKEN_PREFIX) {
+            return AccessTokensService::authenticate(prisma, token).await;
+        }
+
+        let unverified_header = jsonwebtoken::decode_header(token).map_err(|e| {
+            debug!("JWT header is malformed: {:?}", e);
+            AppError::Unauthorized(String::from("JWT validation failed"))
+        })?;
+
+        let key = ctx
+            .config
+            .jwt
+            .verification_key(unverified_header.kid.as_deref())
+            .ok_or_else(|| {
+                debug!("JWT is signed with an unknown key: {:?}", unverified_header.kid);
+                AppError::Unauthorized(String::from("JWT is signed with an unknown key"))
+            })?;
+
         let jwt = jsonwebtoken::decode::<AuthUserClaims>(
             token,
-            &jsonwebtoken::DecodingKey::from_secret(ctx.config.jwt.secret.as_ref()),
-            &jsonwebtoken::Validation::default(),
+            &key.decoding_key,
+            &jsonwebtoken::Validation::new(key.algorithm),
         )
         .map_err(|e| {
             debug!("JWT validation failed: {:?}", e);
@@ -67,7 +188,7 @@ impl AuthUser {
 
         let TokenData { header, claims } = jwt;
 
-        if header.alg != jsonwebtoken::Algorithm::HS256 {
+        if header.alg != key.algorithm {
             debug!("JWT is using the wrong algorithm: {:?}", header.alg);
             return Err(AppError::Unauthorized(String::from(
                 "JWT is using the wrong algorithm",
@@ -79,12 +200,29 @@ impl AuthUser {
             return Err(AppError::Unauthorized(String::from("JWT is expired")));
         }
 
+        if !TokenService::is_session_active(prisma, &claims.sid).await? {
+            debug!("JWT session has been revoked");
+            return Err(AppError::Unauthorized(String::from(
+                "Session has been revoked",
+            )));
+        }
+
         Ok(Self {
             user_id: claims.user_id,
+            session_id: Some(claims.sid),
+            scopes: Scope::ALL.to_vec(),
         })
     }
 }
 
+impl OptionalAuthUser {
+    /// Reads as anonymous unless the token has `scope`, so access tokens only see private
+    /// profiles and their content when they were granted to read them.
+    pub fn with_scope(self, scope: Scope) -> Self {
+        Self(self.0.filter(|auth_user| auth_user.has_scope(scope)))
+    }
+}
+
 impl From<OptionalAuthUser> for Option<AuthUser> {
     fn from(optional_auth_user: OptionalAuthUser) -> Self {
         optional_auth_user.0
@@ -109,7 +247,9 @@ where
                 "Missing Authorization header",
             )))?;
 
-        Self::from_authorization(&ctx, auth_header)
+        let prisma = prisma_from_parts(parts)?;
+
+        Self::from_authorization(&ctx, &prisma, auth_header).await
     }
 }
 
@@ -124,12 +264,130 @@ where
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let ctx: AppContext = AppContext::from_ref(state);
 
+        let auth_header = match parts.headers.get(AUTHORIZATION) {
+            Some(auth_header) => auth_header,
+            None => return Ok(Self(None)),
+        };
+
+        let prisma = prisma_from_parts(parts)?;
+
         Ok(Self(
-            parts
-                .headers
-                .get(AUTHORIZATION)
-                .map(|auth_header| AuthUser::from_authorization(&ctx, auth_header).ok())
-                .flatten(),
+            AuthUser::from_authorization(&ctx, &prisma, auth_header)
+                .await
+                .ok(),
         ))
     }
 }
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+    AppContext: FromRef<S>,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ctx: AppContext = AppContext::from_ref(state);
+
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(USER_AGENT_MAX_LENGTH).collect());
+
+        let request_id = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(REQUEST_ID_MAX_LENGTH).collect());
+
+        if ctx.config.auth.trust_proxy_headers {
+            let forwarded_for = parts
+                .headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| {
+                    ClientInfo::forwarded_ip(value, ctx.config.auth.trusted_proxy_hops)
+                });
+
+            if let Some(ip) = forwarded_for {
+                return Ok(Self {
+                    ip,
+                    user_agent,
+                    request_id,
+                });
+            }
+        }
+
+        let ConnectInfo(addr) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .cloned()
+            .ok_or(AppError::Anyhow(anyhow::anyhow!(
+                "Connection info extension is missing"
+            )))?;
+
+        Ok(Self {
+            ip: addr.ip().to_string(),
+            user_agent,
+            request_id,
+        })
+    }
+}
+
+fn prisma_from_parts(parts: &Parts) -> Result<Arc<PrismaClient>, AppError> {
+    parts
+        .extensions
+        .get::<Arc<PrismaClient>>()
+        .cloned()
+        .ok_or(AppError::Anyhow(anyhow::anyhow!(
+            "Prisma client extension is missing"
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_ip_takes_the_entry_of_the_outermost_proxy() {
+        assert_eq!(
+            ClientInfo::forwarded_ip("203.0.113.7", 1).as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(
+            ClientInfo::forwarded_ip("10.0.0.1, 203.0.113.7, 192.0.2.1", 1).as_deref(),
+            Some("192.0.2.1")
+        );
+        assert_eq!(
+            ClientInfo::forwarded_ip("10.0.0.1, 203.0.113.7, 192.0.2.1", 2).as_deref(),
+            Some("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn forwarded_ip_ignores_entries_added_by_the_client() {
+        // The client sent the first entry, the proxy appended the real address
+        assert_eq!(
+            ClientInfo::forwarded_ip("127.0.0.1,198.51.100.4", 1).as_deref(),
+            Some("198.51.100.4")
+        );
+    }
+
+    #[test]
+    fn forwarded_ip_treats_zero_hops_as_one() {
+        assert_eq!(
+            ClientInfo::forwarded_ip("10.0.0.1, 192.0.2.1", 0).as_deref(),
+            Some("192.0.2.1")
+        );
+    }
+
+    #[test]
+    fn forwarded_ip_needs_an_entry_for_every_hop() {
+        assert_eq!(ClientInfo::forwarded_ip("192.0.2.1", 2), None);
+        assert_eq!(ClientInfo::forwarded_ip("", 1), None);
+        assert_eq!(ClientInfo::forwarded_ip("192.0.2.1, ", 1), None);
+        assert_eq!(ClientInfo::forwarded_ip(" , 192.0.2.1", 2), None);
+    }
+}
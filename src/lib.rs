@@ -3,5 +3,7 @@ pub mod app_error;
 pub mod config;
 pub mod domain;
 pub mod extractor;
+pub mod mailer;
 pub mod prisma;
 pub mod router;
+pub mod storage;
@@ -0,0 +1,35 @@
+use axum::async_trait;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+
+use crate::config::mailer::MailerConfig;
+
+use super::{build_message, Email, Mailer};
+
+/// Writes every email as an `.eml` file into the outbox directory instead of sending it.
+pub struct FileMailer {
+    from: String,
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileMailer {
+    pub fn new(config: &MailerConfig) -> Self {
+        std::fs::create_dir_all(&config.outbox_dir).expect("failed to create mail outbox dir");
+
+        Self {
+            from: config.from.clone(),
+            transport: AsyncFileTransport::new(&config.outbox_dir),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        let message = build_message(&self.from, email)?;
+
+        let id = self.transport.send(message).await?;
+        tracing::info!("email {} written to outbox", id);
+
+        Ok(())
+    }
+}
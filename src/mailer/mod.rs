@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+
+use crate::config::mailer::{MailerConfig, MailerKind};
+
+use self::{file::FileMailer, smtp::SmtpMailer};
+
+pub mod file;
+pub mod smtp;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> anyhow::Result<()>;
+}
+
+pub fn from_config(config: &MailerConfig) -> Arc<dyn Mailer> {
+    match config.kind {
+        MailerKind::Smtp => Arc::new(SmtpMailer::new(config)),
+        MailerKind::File => Arc::new(FileMailer::new(config)),
+    }
+}
+
+fn build_message(from: &str, email: Email) -> anyhow::Result<lettre::Message> {
+    let message = lettre::Message::builder()
+        .from(from.parse()?)
+        .to(email.to.parse()?)
+        .subject(email.subject)
+        .header(lettre::message::header::ContentType::TEXT_PLAIN)
+        .body(email.body)?;
+
+    Ok(message)
+}
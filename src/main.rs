@@ -1,6 +1,7 @@
 #![warn(clippy::all)]
 
 use std::{
+    net::SocketAddr,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -15,14 +16,22 @@ use axum::{
     BoxError, Extension, Json,
 };
 use realworld_axum_prisma::{
-    config::{app_config::AppConfig, AppContext},
+    config::{app_config::AppConfig, storage::StorageKind, AppContext},
+    domain::users::{
+        account::AccountService,
+        password::{BreachedPasswords, PasswordHashing},
+    },
+    mailer,
     prisma::PrismaClient,
     router::AppRouter,
+    storage::{self, local::LOCAL_STORAGE_ROUTE},
 };
 use serde_json::json;
 use tower::ServiceBuilder;
 use tower_http::{
     cors::{Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
     trace::TraceLayer,
 };
 use tracing::info;
@@ -31,8 +40,14 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let config = AppConfig::init();
+    PasswordHashing::check_config(&config.password_hashing)?;
     let app_context = AppContext {
         config: Arc::new(config.clone()),
+        mailer: mailer::from_config(&config.mailer),
+        breached_passwords: Arc::new(BreachedPasswords::load(
+            config.password.breached_passwords_file.as_deref(),
+        )?),
+        storage: storage::from_config(&config.storage)?,
     };
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(&config.log_level))
@@ -41,9 +56,22 @@ async fn main() -> anyhow::Result<()> {
 
     let prisma_client = Arc::new(PrismaClient::_builder().build().await?);
 
+    tokio::spawn(AccountService::run_purge(
+        prisma_client.clone(),
+        app_context.config.clone(),
+    ));
+
     let cors = CorsLayer::new().allow_methods(Any).allow_headers(Any).allow_origin(Any);
 
-    let app = AppRouter::new()
+    let mut app = AppRouter::new();
+    if let StorageKind::Local = config.storage.kind {
+        app = app.nest_service(
+            LOCAL_STORAGE_ROUTE,
+            ServeDir::new(&config.storage.local_dir),
+        );
+    }
+
+    let app = app
         .layer(cors)
         .layer(Extension(prisma_client))
         .layer(
@@ -52,6 +80,9 @@ async fn main() -> anyhow::Result<()> {
                 .layer(HandleErrorLayer::new(handle_timeout_error))
                 .timeout(Duration::from_secs(30)),
         )
+        // Outermost, so every request carries an id that is echoed in the response
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         .route_layer(middleware::from_fn(track_metrics))
         .with_state(app_context);
 
@@ -60,9 +91,12 @@ async fn main() -> anyhow::Result<()> {
     let listener = tokio::net::TcpListener::bind(&format!("0.0.0.0:{}", config.port))
         .await
         .unwrap();
-    axum::serve(listener, app)
-        .await
-        .context("error while booting server")?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("error while booting server")?;
 
     Ok(())
 }
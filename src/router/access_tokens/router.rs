@@ -0,0 +1,23 @@
+use axum::{
+    routing::{delete, get, post},
+    Router,
+};
+
+use crate::{config::AppContext, domain::access_tokens::service::AccessTokensService};
+
+pub struct AccessTokensRouter;
+
+impl AccessTokensRouter {
+    pub fn new() -> Router<AppContext> {
+        Router::new()
+            .route(
+                "/user/tokens",
+                post(AccessTokensService::create_access_token),
+            )
+            .route("/user/tokens", get(AccessTokensService::get_access_tokens))
+            .route(
+                "/user/tokens/:id",
+                delete(AccessTokensService::delete_access_token),
+            )
+    }
+}
@@ -0,0 +1,13 @@
+use axum::{routing::get, Router};
+
+use crate::{config::AppContext, domain::audit::service::AuditService};
+
+pub struct AuditRouter;
+
+impl AuditRouter {
+    pub fn new() -> Router<AppContext> {
+        Router::new()
+            .route("/user/audit", get(AuditService::get_own_events))
+            .route("/audit", get(AuditService::search_events))
+    }
+}
@@ -0,0 +1,11 @@
+use axum::{routing::get, Router};
+
+use crate::{config::AppContext, domain::jwks::service::JwksService};
+
+pub struct JwksRouter;
+
+impl JwksRouter {
+    pub fn new() -> Router<AppContext> {
+        Router::new().route("/jwks.json", get(JwksService::get_jwks))
+    }
+}
@@ -1,4 +1,7 @@
+pub mod access_tokens;
 pub mod articles;
+pub mod audit;
+pub mod jwks;
 pub mod profiles;
 pub mod tags;
 pub mod users;
@@ -9,7 +12,9 @@ use users::router::UsersRouter;
 use crate::config::AppContext;
 
 use self::{
-    articles::router::ArticlesRouter, profiles::router::ProfilesRouter, tags::router::TagsRouter,
+    access_tokens::router::AccessTokensRouter, articles::router::ArticlesRouter,
+    audit::router::AuditRouter, jwks::router::JwksRouter, profiles::router::ProfilesRouter,
+    tags::router::TagsRouter,
 };
 
 pub struct AppRouter;
@@ -18,10 +23,13 @@ impl AppRouter {
     pub fn new() -> Router<AppContext> {
         Router::new()
             .route("/", get(hello))
+            .nest("/.well-known", JwksRouter::new())
             .nest("/api", UsersRouter::new())
+            .nest("/api", AccessTokensRouter::new())
             .nest("/api", ProfilesRouter::new())
             .nest("/api", ArticlesRouter::new())
             .nest("/api", TagsRouter::new())
+            .nest("/api", AuditRouter::new())
     }
 }
 
@@ -3,7 +3,14 @@ use axum::{
     Router,
 };
 
-use crate::{config::AppContext, domain::profiles::service::ProfilesService};
+use crate::{
+    config::AppContext,
+    domain::profiles::{
+        follow_request::FollowRequestsService, follows::FollowsService,
+        relationship::RelationshipsService, service::ProfilesService,
+        suggestion::SuggestionsService,
+    },
+};
 
 pub struct ProfilesRouter;
 
@@ -19,5 +26,45 @@ impl ProfilesRouter {
                 "/profiles/:username/follow",
                 delete(ProfilesService::unfollow_profile),
             )
+            .route(
+                "/profiles/:username/followers",
+                get(FollowsService::get_followers),
+            )
+            .route(
+                "/profiles/:username/following",
+                get(FollowsService::get_following),
+            )
+            .route(
+                "/profiles/:username/block",
+                post(RelationshipsService::block_profile),
+            )
+            .route(
+                "/profiles/:username/block",
+                delete(RelationshipsService::unblock_profile),
+            )
+            .route(
+                "/profiles/:username/mute",
+                post(RelationshipsService::mute_profile),
+            )
+            .route(
+                "/profiles/:username/mute",
+                delete(RelationshipsService::unmute_profile),
+            )
+            .route(
+                "/user/suggestions",
+                get(SuggestionsService::get_suggestions),
+            )
+            .route(
+                "/user/follow-requests",
+                get(FollowRequestsService::get_follow_requests),
+            )
+            .route(
+                "/user/follow-requests/:username/approve",
+                post(FollowRequestsService::approve_follow_request),
+            )
+            .route(
+                "/user/follow-requests/:username/reject",
+                post(FollowRequestsService::reject_follow_request),
+            )
     }
 }
@@ -1,9 +1,22 @@
 use axum::{
-    routing::{get, post, put},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post, put},
     Router,
 };
 
-use crate::{config::AppContext, domain::users::service::UsersService};
+use crate::{
+    config::AppContext,
+    domain::users::{
+        account::AccountService,
+        avatar::{AvatarService, MAX_AVATAR_BYTES},
+        export::ExportService,
+        oidc::OidcService,
+        service::UsersService,
+        session::SessionsService,
+        settings::SettingsService,
+        two_factor::TwoFactorService,
+    },
+};
 
 pub struct UsersRouter;
 
@@ -13,6 +26,38 @@ impl UsersRouter {
             .route("/user", get(UsersService::get_current_user))
             .route("/users", post(UsersService::create_user))
             .route("/users/login", post(UsersService::login))
+            .route("/users/login/2fa", post(UsersService::login_two_factor))
+            .route("/users/oidc/authorize", get(OidcService::authorize))
+            .route("/users/oidc/callback", post(OidcService::callback))
+            .route("/users/refresh", post(UsersService::refresh))
+            .route("/users/logout", post(UsersService::logout))
+            .route("/users/logout/all", post(UsersService::logout_all))
+            .route(
+                "/users/password/forgot",
+                post(UsersService::forgot_password),
+            )
+            .route("/users/password/reset", post(UsersService::reset_password))
+            .route("/users/email/verify", post(UsersService::verify_email))
+            .route(
+                "/user/email/verification",
+                post(UsersService::resend_verification_email),
+            )
             .route("/user", put(UsersService::update_user))
+            .route("/user", delete(AccountService::delete_account))
+            .route(
+                "/user/avatar",
+                post(AvatarService::upload_avatar).layer(DefaultBodyLimit::max(MAX_AVATAR_BYTES)),
+            )
+            .route("/user/export", get(ExportService::export))
+            .route("/user/settings", get(SettingsService::get_settings))
+            .route("/user/settings", put(SettingsService::update_settings))
+            .route("/user/2fa/enroll", post(TwoFactorService::enroll))
+            .route("/user/2fa/confirm", post(TwoFactorService::confirm))
+            .route("/user/2fa", delete(TwoFactorService::disable))
+            .route("/user/sessions", get(SessionsService::get_sessions))
+            .route(
+                "/user/sessions/:id",
+                delete(SessionsService::delete_session),
+            )
     }
 }
@@ -0,0 +1,51 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use axum::async_trait;
+
+use crate::config::storage::StorageConfig;
+
+use super::Storage;
+
+/// Route the stored files are served from.
+pub const LOCAL_STORAGE_ROUTE: &str = "/uploads";
+
+/// Writes files below a local directory, for development and single-node setups.
+pub struct LocalStorage {
+    dir: PathBuf,
+    public_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(config: &StorageConfig) -> Self {
+        Self {
+            dir: PathBuf::from(&config.local_dir),
+            public_url: config.public_url.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, contents: Vec<u8>, _content_type: &str) -> anyhow::Result<()> {
+        let path = self.dir.join(key);
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::write(path, contents).await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.dir.join(key)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
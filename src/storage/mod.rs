@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+
+use crate::config::storage::{StorageConfig, StorageKind};
+
+use self::{local::LocalStorage, s3::S3Storage};
+
+pub mod local;
+pub mod s3;
+
+/// Blob storage for user uploads, addressed by slash-separated keys.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, contents: Vec<u8>, content_type: &str) -> anyhow::Result<()>;
+
+    /// Deleting a missing file is not an error.
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+
+    /// Public URL the file is served from.
+    fn url(&self, key: &str) -> String;
+}
+
+pub fn from_config(config: &StorageConfig) -> anyhow::Result<Arc<dyn Storage>> {
+    Ok(match config.kind {
+        StorageKind::Local => Arc::new(LocalStorage::new(config)),
+        StorageKind::S3 => Arc::new(S3Storage::new(config)?),
+    })
+}
@@ -0,0 +1,59 @@
+use axum::async_trait;
+use s3::{creds::Credentials, Bucket, Region};
+
+use crate::config::storage::StorageConfig;
+
+use super::Storage;
+
+/// Stores files in an S3-compatible bucket such as AWS S3, MinIO or R2.
+pub struct S3Storage {
+    bucket: Box<Bucket>,
+    public_url: String,
+}
+
+impl S3Storage {
+    pub fn new(config: &StorageConfig) -> anyhow::Result<Self> {
+        let region = Region::Custom {
+            region: config.s3_region.clone(),
+            endpoint: config.s3_endpoint.clone(),
+        };
+        let credentials = Credentials {
+            access_key: config.s3_access_key_id.clone(),
+            secret_key: config.s3_secret_access_key.clone(),
+            security_token: None,
+            session_token: None,
+            expiration: None,
+        };
+
+        let mut bucket = Bucket::new(&config.s3_bucket, region, credentials)?;
+        if config.s3_path_style {
+            bucket = bucket.with_path_style();
+        }
+
+        Ok(Self {
+            bucket,
+            public_url: config.public_url.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, contents: Vec<u8>, content_type: &str) -> anyhow::Result<()> {
+        self.bucket
+            .put_object_with_content_type(key, &contents, content_type)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.bucket.delete_object(key).await?;
+
+        Ok(())
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
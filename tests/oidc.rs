@@ -0,0 +1,186 @@
+//! Runs the OIDC login against the mock provider and a real database:
+//!
+//! ```bash
+//! docker-compose up -d database
+//! docker-compose --profile oidc up -d mock-idp
+//! cargo prisma migrate deploy
+//! cargo test --test oidc -- --ignored
+//! ```
+
+use std::sync::Arc;
+
+use axum::{extract::State, Extension, Json};
+use realworld_axum_prisma::{
+    config::{app_config::AppConfig, AppContext},
+    domain::users::{
+        oidc::OidcService,
+        password::{BreachedPasswords, PasswordHashing},
+        request::OidcCallbackInput,
+        response::LoginResponse,
+        token::TokenService,
+        username::Username,
+        OidcBody,
+    },
+    extractor::ClientInfo,
+    mailer,
+    prisma::{personal_access_token, session, user, PrismaClient},
+    storage,
+};
+use serde_json::json;
+
+const MOCK_ISSUER: &str = "http://localhost:8080/default";
+
+async fn setup() -> (Arc<PrismaClient>, AppContext) {
+    if std::env::var("OIDC_ISSUER").is_err() {
+        std::env::set_var("OIDC_ISSUER", MOCK_ISSUER);
+        std::env::set_var("OIDC_CLIENT_ID", "conduit");
+    }
+
+    let config = AppConfig::init();
+    let ctx = AppContext {
+        config: Arc::new(config.clone()),
+        mailer: mailer::from_config(&config.mailer),
+        breached_passwords: Arc::new(BreachedPasswords::default()),
+        storage: storage::from_config(&config.storage).unwrap(),
+    };
+    let prisma = Arc::new(PrismaClient::_builder().build().await.unwrap());
+
+    (prisma, ctx)
+}
+
+fn client() -> ClientInfo {
+    ClientInfo {
+        ip: "127.0.0.1".to_string(),
+        user_agent: Some("oidc-test".to_string()),
+        request_id: None,
+    }
+}
+
+/// Logs in at the mock provider as `subject` and returns the code and state it redirects with.
+async fn provider_login(authorization_url: &str, subject: &str, email: &str) -> (String, String) {
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .post(authorization_url)
+        .form(&[
+            ("username", subject.to_string()),
+            (
+                "claims",
+                json!({ "email": email, "email_verified": true }).to_string(),
+            ),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .expect("the mock provider should redirect back")
+        .to_str()
+        .unwrap();
+    let location = reqwest::Url::parse(location).unwrap();
+    let param = |name: &str| {
+        location
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.to_string())
+            .unwrap()
+    };
+
+    (param("code"), param("state"))
+}
+
+#[tokio::test]
+#[ignore = "needs the database and the mock OIDC provider from docker-compose"]
+async fn linking_an_unverified_account_drops_its_credentials() {
+    let (prisma, ctx) = setup().await;
+    let suffix = TokenService::random_string(8).to_lowercase();
+    let email = format!("victim-{}@example.com", suffix);
+    let username = format!("squatter-{}", suffix);
+
+    // Someone else registered the address first and never verified it
+    let password = PasswordHashing::hash(&ctx, "attacker-password")
+        .await
+        .unwrap();
+    let squatter = prisma
+        .user()
+        .create(
+            email.clone(),
+            username.clone(),
+            Username::canonical(&username),
+            vec![
+                user::password::set(Some(password)),
+                user::totp_secret::set(Some("JBSWY3DPEHPK3PXP".to_string())),
+            ],
+        )
+        .exec()
+        .await
+        .unwrap();
+    TokenService::issue(&prisma, &ctx, squatter.id, &client())
+        .await
+        .unwrap();
+    prisma
+        .personal_access_token()
+        .create(
+            "squatter".to_string(),
+            format!("hash-{}", suffix),
+            "articles:write".to_string(),
+            user::id::equals(squatter.id),
+            vec![],
+        )
+        .exec()
+        .await
+        .unwrap();
+
+    let Json(authorization) = OidcService::authorize(Extension(prisma.clone()), State(ctx.clone()))
+        .await
+        .unwrap();
+    let (code, state) = provider_login(
+        &authorization.oidc.authorization_url,
+        &format!("victim-{}", suffix),
+        &email,
+    )
+    .await;
+
+    let Json(body) = OidcService::callback(
+        Extension(prisma.clone()),
+        State(ctx.clone()),
+        client(),
+        Json(OidcBody {
+            oidc: OidcCallbackInput { code, state },
+        }),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(body.user, LoginResponse::User(_)));
+
+    let linked = prisma
+        .user()
+        .find_unique(user::id::equals(squatter.id))
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(linked.email_verified);
+    assert_eq!(linked.password, None);
+    assert_eq!(linked.totp_secret, None);
+
+    let tokens = prisma
+        .personal_access_token()
+        .count(vec![personal_access_token::user_id::equals(squatter.id)])
+        .exec()
+        .await
+        .unwrap();
+    assert_eq!(tokens, 0);
+
+    // Only the session of the OIDC login itself is left
+    let sessions = prisma
+        .session()
+        .count(vec![session::user_id::equals(squatter.id)])
+        .exec()
+        .await
+        .unwrap();
+    assert_eq!(sessions, 1);
+}